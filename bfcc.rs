@@ -1,16 +1,58 @@
 extern crate llvm_ir;
+extern crate serde;
+extern crate serde_json;
+
+// 2015 paths start at the crate root and we're `mod bfcc` in every bin
+use self::serde::Serialize;
+use self::serde::Serializer;
 
 use std::fmt;
 
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::ops::Deref;
 use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use std::convert::TryFrom;
 use std::convert::TryInto;
 
+// Every function costs a FuncMask cell in every frame, a slot in the ret pad,
+// and a whole body in the main loop, whether anybody calls it or not. So walk
+// the call graph out from the entry and toss whatever we never reach.
+fn drop_unreachable_funcs(module: &mut llvm_ir::Module, entry: &str) {
+	let mut reachable: Vec<String> = vec![];
+	let mut todo = vec![entry.to_string()];
+
+	while let Some(name) = todo.pop() {
+		if reachable.contains(&name) {
+			continue;
+		}
+
+		let func = match module.get_func_by_name(&name) {
+			Some(f) => f,
+			None => continue,
+		};
+
+		for i in func.basic_blocks.iter().flat_map(|b| b.instrs.iter()) {
+			if let llvm_ir::Instruction::Call(c) = i {
+				if let Some(callee) = callee_name(c) {
+					todo.push(callee);
+				}
+			}
+		}
+
+		reachable.push(name);
+	}
+
+	module.functions.retain(|f| reachable.contains(&f.name));
+}
+
 // Split all blocks at calls. This should result in all calls treated sorta like
 // terminator instructions being the last instruction of their block before a
 // unconditional branch.
@@ -28,10 +70,9 @@ fn calls_terminate_blocks(module: &mut llvm_ir::Module) {
 		while block < func.basic_blocks.len() {
 			let mut instr = 0;
 			while instr < func.basic_blocks[block].instrs.len() {
-				if let llvm_ir::Instruction::Call(_) =
-					func.basic_blocks[block].instrs[instr]
-				{
-				} else {
+				// intrinsics happen right where they are, no need to go
+				// anywhere
+				if !leaves_frame(&func.basic_blocks[block].instrs[instr]) {
 					instr += 1;
 					continue;
 				}
@@ -120,10 +161,7 @@ fn calls_terminate_blocks(module: &mut llvm_ir::Module) {
 // in the calling function with the callee's mask set.
 fn calls_never_in_first_block(module: &mut llvm_ir::Module) {
 	for func in module.functions.iter_mut() {
-		let hascall = func.basic_blocks[0].instrs.iter().any(|i| match i {
-			llvm_ir::Instruction::Call(_) => true,
-			_ => false,
-		});
+		let hascall = func.basic_blocks[0].instrs.iter().any(leaves_frame);
 
 		if !hascall {
 			continue;
@@ -148,22 +186,131 @@ fn calls_never_in_first_block(module: &mut llvm_ir::Module) {
 	}
 }
 
+fn block_succs(block: &llvm_ir::BasicBlock) -> Vec<&llvm_ir::Name> {
+	match &block.term {
+		llvm_ir::Terminator::Br(br) => vec![&br.dest],
+		llvm_ir::Terminator::CondBr(cbr) => {
+			vec![&cbr.true_dest, &cbr.false_dest]
+		}
+		_ => vec![],
+	}
+}
+
+// every edge that closes a loop, as (from, to) block indices
+fn back_edges(func: &llvm_ir::Function) -> Vec<(usize, usize)> {
+	let idx = |n: &llvm_ir::Name| {
+		func.basic_blocks.iter().position(|b| &b.name == n).unwrap()
+	};
+
+	let mut back = vec![];
+	let mut seen = vec![false; func.basic_blocks.len()];
+	let mut path = vec![];
+	// (block, how many of its successors we've been through)
+	let mut stack = vec![(0, 0)];
+	seen[0] = true;
+	path.push(0);
+
+	while let Some((b, next)) = stack.pop() {
+		let succs = block_succs(&func.basic_blocks[b]);
+		if next == succs.len() {
+			path.pop();
+			continue;
+		}
+
+		stack.push((b, next + 1));
+
+		let s = idx(succs[next]);
+		if path.contains(&s) {
+			back.push((b, s));
+		} else if !seen[s] {
+			seen[s] = true;
+			path.push(s);
+			stack.push((s, 0));
+		}
+	}
+
+	back
+}
+
+// A block whose mask gets set by a block before it in the function loop runs
+// in the same trip around, anything earlier waits for the next one. So put
+// every block after all the blocks that branch to it, loops' back edges aside,
+// and every other branch falls straight through.
+//
+// Whenever there's a choice the earliest block in source order goes first,
+// clang's order is usually fine already.
+fn block_order(func: &llvm_ir::Function) -> Vec<usize> {
+	let n = func.basic_blocks.len();
+	let back = back_edges(func);
+
+	let mut preds = vec![0; n];
+	let mut succs = vec![vec![]; n];
+	for (b, block) in func.basic_blocks.iter().enumerate() {
+		for s in block_succs(block) {
+			let s = func
+				.basic_blocks
+				.iter()
+				.position(|bb| &bb.name == s)
+				.unwrap();
+
+			if !back.contains(&(b, s)) {
+				preds[s] += 1;
+				succs[b].push(s);
+			}
+		}
+	}
+
+	let mut order = vec![];
+	let mut ready = vec![0];
+	while !ready.is_empty() {
+		ready.sort();
+		let b = ready.remove(0);
+		order.push(b);
+
+		for &s in succs[b].iter() {
+			preds[s] -= 1;
+			if preds[s] == 0 {
+				ready.push(s);
+			}
+		}
+	}
+
+	// unreachable stuff can tag along at the end
+	for b in 0..n {
+		if !order.contains(&b) {
+			order.push(b);
+		}
+	}
+
+	order
+}
+
 #[derive(Debug, Clone)]
 struct Addr {
 	v: Rc<RefCell<Addrt>>,
 }
 
+// resolved down to a cell when it can be, otherwise the whole chain
+impl Serialize for Addr {
+	fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+		match try_resaddr(self) {
+			Some(a) => s.serialize_u64(a as u64),
+			None => self.v.borrow().serialize(s),
+		}
+	}
+}
+
 impl fmt::Display for Addr {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(f, ".{:?}", self.v.borrow())
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 enum Addrt {
 	Fixed(usize),
 	Offset(Addr, i64),
-	Named(llvm_ir::Name),
+	Named(#[serde(serialize_with = "ser_name")] llvm_ir::Name),
 }
 
 // type Addr = Rc<RefCell<Addrt>>;
@@ -182,13 +329,26 @@ fn resaddr(s: Addr) -> usize {
 	}
 }
 
+fn try_resaddr(s: &Addr) -> Option<usize> {
+	match s.v.deref().borrow().deref() {
+		Addrt::Fixed(u) => Some(*u),
+		Addrt::Offset(a, o) => try_resaddr(a).map(|a| (a as i64 + o) as usize),
+		Addrt::Named(_) => None,
+	}
+}
+
+// llvm_ir doesn't do serde, names go out the way they're printed
+fn ser_name<S: Serializer>(n: &llvm_ir::Name, s: S) -> Result<S::Ok, S::Error> {
+	s.serialize_str(&n.to_string())
+}
+
 fn fixed_addr(a: usize) -> Addr {
 	Addr {
 		v: Rc::new(RefCell::new(Addrt::Fixed(a))),
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 enum BfOp {
 	// kinda actual brainfuck, these do fucky stuff to the instr pointer
 	// future passes can't understand
@@ -214,6 +374,7 @@ enum BfOp {
 	// the only architecture with a real mov instruction
 	Mov(Addr, Addr), // *a -> *b : a will be zeroed, b must be zero
 	Putch(Addr),
+	Getch(Addr), // *a must be zero, stays zero on EOF
 	Zero(Addr),
 	Loop(Addr, Vec<BfOp>),
 
@@ -222,6 +383,25 @@ enum BfOp {
 	Comment(String),   // if you see something say something
 
 	Nop,
+
+	// only lives until the end of build_func, or build for the stack guard
+	Frame(FrameOp),
+}
+
+// the couple of ops that need the frame's width rather than just somewhere
+// in it. Codegen doesn't know that until it's done borrowing scratch.
+#[derive(Debug, Clone, Serialize)]
+enum FrameOp {
+	Right,             // Right(stack_width)
+	Left,              // Left(stack_width)
+	AddI(Addr, usize), // AddI(a, stack_width + n)
+	// AddI(a, furthest the stack pointer can be for the callee's frame to
+	// still fit in the budget), n being how far the callee's sp is past ours
+	// minus the frame. Needs the callee's width too so build does this one.
+	Budget(Addr, String, usize),
+	// Mov(a + the callee's width, b), a return value left right past the
+	// callee's frame. Also on build.
+	Returned(Addr, String, Addr),
 }
 
 // have to promise to give registers before you take them otherwise
@@ -329,6 +509,20 @@ fn zero_frame(ctx: &mut Ctx) -> Vec<BfOp> {
 			.collect(),
 	);
 
+	// whatever va_arg didn't get to
+	ops.append(
+		&mut ctx
+			.layout
+			.iter()
+			.enumerate()
+			.filter(|(_, c)| match c {
+				Cell::VarArg(_) => true,
+				_ => false,
+			})
+			.map(|(i, _)| BfOp::Zero(fixed_addr(i)))
+			.collect(),
+	);
+
 	ops
 }
 
@@ -635,27 +829,95 @@ fn build_icmp<'a>(
 	icmp_out
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 enum Cell {
 	//Args,
 	//StackPtr,
 	MainLoop,
 
 	FuncMask(String),
-	BlockMask(llvm_ir::Name),
+	BlockMask(#[serde(serialize_with = "ser_name")] llvm_ir::Name),
 
 	Borrowed(Box<Cell>),
 
-	Alloc(llvm_ir::Name),
-	Reg { n: llvm_ir::Name, multi_use: bool },
+	Alloc(#[serde(serialize_with = "ser_name")] llvm_ir::Name),
+	// a variadic function's extra args, moved in from the jump frame so
+	// va_arg can walk them like an array
+	VarArg(usize),
+	Reg {
+		#[serde(serialize_with = "ser_name")]
+		n: llvm_ir::Name,
+		multi_use: bool,
+	},
 
 	Free,
 }
 
+impl fmt::Display for Cell {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Cell::MainLoop => write!(f, "mainloop"),
+			Cell::FuncMask(n) => write!(f, "F:{}", n),
+			Cell::BlockMask(n) => write!(f, "B:{}", n),
+			Cell::Borrowed(c) => write!(f, "borrowed({})", c),
+			Cell::Alloc(n) => write!(f, "alloc {}", n),
+			Cell::VarArg(i) => write!(f, "vararg {}", i),
+			Cell::Reg { n, multi_use } => write!(
+				f,
+				"reg {}{}",
+				n,
+				if *multi_use { " (mult)" } else { "" }
+			),
+			Cell::Free => write!(f, "free"),
+		}
+	}
+}
+
 type Layout = Vec<Cell>;
 
 const STACK_PTR_W: usize = 1;
 
+// Between a caller's frame and its callee's sits a jump frame: the caller's
+// jump pad, up through the ret landing pad's mask, the callee's args, then
+// its stack pointer. The width only depends on the callee so a ret never
+// needs to know how it got called.
+fn jump_frame_width(ret_pad_width: usize, args: usize) -> usize {
+	ret_pad_width + 1 + args + STACK_PTR_W
+}
+
+// Arg slots in the jump frame below each function. That's its params, or for
+// a variadic one the most args any call hands it.
+fn arg_widths(module: &llvm_ir::Module) -> Vec<(String, usize)> {
+	let calls: Vec<(String, usize)> = module
+		.functions
+		.iter()
+		.flat_map(|f| f.basic_blocks.iter())
+		.flat_map(|b| b.instrs.iter())
+		.filter(|i| leaves_frame(i))
+		.filter_map(|i| match i {
+			llvm_ir::Instruction::Call(c) => {
+				callee_name(c).map(|n| (n, c.arguments.len()))
+			}
+			_ => None,
+		})
+		.collect();
+
+	module
+		.functions
+		.iter()
+		.map(|f| {
+			let most = calls
+				.iter()
+				.filter(|(n, _)| n == &f.name)
+				.map(|(_, args)| *args)
+				.chain(Some(f.parameters.len()))
+				.max()
+				.unwrap();
+			(f.name.clone(), most)
+		})
+		.collect()
+}
+
 // How do you deref an address when you're lost in a sea of tape?
 fn build_ptr_train(
 	ctx: &mut Ctx,
@@ -889,11 +1151,48 @@ struct Ctx {
 	layout: Layout,
 	addrs: Vec<Addr>,
 	ret_pad_width: Option<usize>,
-	stack_width: Option<usize>,
+	// every function's arg slots, sizes the jump frame below it
+	arg_widths: Vec<(String, usize)>,
+	// first cell past the frame. Named until codegen is done borrowing
+	// scratch and we know how wide the frame turned out
+	frame_end: Option<Addr>,
 	entry_block_addr: Option<usize>,
 	retpad_addr: Option<Addr>,
 	ownfid: Option<usize>,
 	globals: Vec<GlobalMap>,
+	// check there's room for the callee's frame before every call
+	stack_guard: bool,
+}
+
+impl Ctx {
+	fn arg_width(&self, func: &str) -> usize {
+		self.arg_widths.iter().find(|(f, _)| f == func).unwrap().1
+	}
+
+	// width of the jump frame right below a frame of `func`
+	fn jump_width(&self, func: &str) -> usize {
+		jump_frame_width(self.ret_pad_width.unwrap(), self.arg_width(func))
+	}
+}
+
+fn cell_addr(ctx: &Ctx, cell: &Cell) -> Addr {
+	fixed_addr(ctx.layout.iter().position(|c| c == cell).unwrap())
+}
+
+// make `name` the block that runs next in this frame
+fn goto_block(
+	ctx: &Ctx,
+	name: &llvm_ir::Name,
+	tag: Option<String>,
+) -> Vec<BfOp> {
+	let mask = cell_addr(ctx, &Cell::BlockMask(name.clone()));
+
+	let mut ops = vec![];
+	if let Some(t) = tag {
+		ops.push(BfOp::Tag(mask.clone(), t));
+	}
+	ops.push(BfOp::AddI(mask, 1));
+	ops
 }
 
 enum RetMeta {
@@ -923,6 +1222,25 @@ struct InstrMeta<'a> {
 	)],
 }
 
+// how much to take off for a constant that's negative at its own width, mod
+// 256 since that's all a cell holds
+fn neg_const(op: &llvm_ir::Operand) -> Option<u8> {
+	match op {
+		llvm_ir::Operand::ConstantOperand(c) => match c.deref() {
+			llvm_ir::constant::Constant::Int { bits, value } => {
+				let v = ((*value << (64 - bits)) as i64) >> (64 - bits);
+				if v < 0 {
+					Some(v.unsigned_abs() as u8)
+				} else {
+					None
+				}
+			}
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
 fn build_getelemptr(
 	ctx: &mut Ctx,
 	i: &llvm_ir::Instruction,
@@ -932,6 +1250,19 @@ fn build_getelemptr(
 ) -> Vec<BfOp> {
 	let dest = ret.unwrap();
 
+	// p - 1 comes in as an index of -1. Pointers never wrap so just take it off
+	let gep: llvm_ir::instruction::GetElementPtr = i.clone().try_into().unwrap();
+	if let Some(n) = neg_const(&gep.indices[0]) {
+		let (op0, o0) = builder_args_to_consumable_reg(ctx, &args[0]);
+		return o0
+			.into_iter()
+			.chain(vec![
+				BfOp::Mov(op0.clone(), dest.clone()),
+				BfOp::SubI(dest, n),
+			])
+			.collect();
+	}
+
 	let (op0, o0) = builder_args_to_consumable_reg(ctx, &args[0]);
 	let (op1, o1) = builder_args_to_consumable_reg(ctx, &args[1]);
 
@@ -962,17 +1293,12 @@ fn build_sub(
 
 	let dest = ret.unwrap();
 
+	// wraps like build_add, -x comes out as 0 - x
 	vec![]
 		.into_iter()
 		.chain(o0)
 		.chain(o1)
-		.chain(vec![
-			BfOp::Mov(op0.clone(), dest.clone()),
-			BfOp::Loop(
-				op1.clone(),
-				vec![BfOp::SubI(op1.clone(), 1), BfOp::SubI(dest.clone(), 1)],
-			),
-		])
+		.chain(wrapping_sub(ctx, op0, op1, dest))
 		.collect()
 }
 
@@ -1554,21 +1880,105 @@ fn build_add(
 ) -> Vec<BfOp> {
 	let dest = ret.unwrap();
 
-	let (op0, o0) = builder_args_to_consumable_reg(ctx, &args[0]);
-	let (op1, o1) = builder_args_to_consumable_reg(ctx, &args[1]);
+	// An add can wrap in the ir even when the c never did. clang writes
+	// x - 1 as x + -1 and x - 149 as x + 107 once it's optimizing, a - b can
+	// come out as (b ^ 102) + 1 + (a ^ 152) and x % 221 as
+	// x < 221 ? x : x + 35. So they all wrap, bar + 1 which is every loop
+	// counter and would have to be x - 255.
+	let add: llvm_ir::instruction::Add = i.clone().try_into().unwrap();
+	let (op0, mut ops) = builder_args_to_consumable_reg(ctx, &args[0]);
+	match add.operand1 {
+		llvm_ir::Operand::ConstantOperand(_) => {
+			let k = uncop(ctx, &add.operand1) as u8;
+			if k != 1 {
+				ops.append(&mut wrapping_addi(ctx, op0, k, dest));
+				return ops;
+			}
+		}
+		_ => {
+			let (op1, mut o1) = builder_args_to_consumable_reg(ctx, &args[1]);
+			let room = borrow_reg(ctx, 1);
+			ops.append(&mut o1);
+			ops.push(BfOp::AddI(room.clone(), 255));
+			ops.push(BfOp::Loop(
+				op1.clone(),
+				vec![BfOp::SubI(op1.clone(), 1), BfOp::SubI(room.clone(), 1)],
+			));
+			ops.append(&mut wrapping_add(ctx, op0, room, dest));
+			return ops;
+		}
+	}
 
-	vec![]
-		.into_iter()
-		.chain(o0)
-		.chain(o1)
-		.chain(vec![
-			BfOp::Mov(op0.clone(), dest.clone()),
+	let (op1, mut o1) = builder_args_to_consumable_reg(ctx, &args[1]);
+	ops.append(&mut o1);
+	ops.push(BfOp::Mov(op0.clone(), dest.clone()));
+	ops.push(BfOp::Loop(
+		op1.clone(),
+		vec![BfOp::SubI(op1.clone(), 1), BfOp::AddI(dest.clone(), 1)],
+	));
+	ops
+}
+
+// x + y mod 256 without ever leaving 0..=255, given room holding 255 - y, how
+// far x can go before it wraps. subnu takes x off that. Whatever room's left
+// means it fit and the underflow is how far past 255 it went.
+fn wrapping_add(ctx: &mut Ctx, x: Addr, room: Addr, dest: Addr) -> Vec<BfOp> {
+	let over = borrow_reg(ctx, 1);
+	let (mut ops, left) = subnu(ctx, room, x, Some(over.clone()));
+	ops.push(BfOp::AddI(dest.clone(), 255));
+	ops.push(BfOp::Loop(
+		left.clone(),
+		vec![BfOp::SubI(left.clone(), 1), BfOp::SubI(dest.clone(), 1)],
+	));
+	ops.push(BfOp::Loop(
+		over.clone(),
+		vec![
+			BfOp::Zero(dest.clone()),
+			BfOp::SubI(over.clone(), 1),
+			BfOp::Mov(over, dest),
+		],
+	));
+	ops
+}
+
+// same for a constant. When 256 - k is small, like it is for x - 1, that's
+// cheaper as x - (256 - k).
+fn wrapping_addi(ctx: &mut Ctx, x: Addr, k: u8, dest: Addr) -> Vec<BfOp> {
+	if k == 0 {
+		return vec![BfOp::Mov(x, dest)];
+	}
+
+	let konst = borrow_reg(ctx, 1);
+	let m = (256 - k as usize) as u8;
+	if m > 128 {
+		let mut ops = vec![BfOp::AddI(konst.clone(), 255 - k)];
+		ops.append(&mut wrapping_add(ctx, x, konst, dest));
+		return ops;
+	}
+
+	let mut ops = vec![BfOp::AddI(konst.clone(), m)];
+	ops.append(&mut wrapping_sub(ctx, x, konst, dest));
+	ops
+}
+
+// x - y mod 256. subnu leaves what's left of x, and if it came up short it
+// wrapped to 256 less that.
+fn wrapping_sub(ctx: &mut Ctx, x: Addr, y: Addr, dest: Addr) -> Vec<BfOp> {
+	let short = borrow_reg(ctx, 1);
+	let (mut ops, left) = subnu(ctx, x, y, Some(short.clone()));
+	ops.push(BfOp::Mov(left, dest.clone()));
+	ops.push(BfOp::Loop(
+		short.clone(),
+		vec![
+			BfOp::AddI(dest.clone(), 255),
 			BfOp::Loop(
-				op1.clone(),
-				vec![BfOp::SubI(op1.clone(), 1), BfOp::AddI(dest.clone(), 1)],
+				short.clone(),
+				vec![BfOp::SubI(short.clone(), 1), BfOp::SubI(dest.clone(), 1)],
 			),
-		])
-		.collect()
+			BfOp::AddI(dest, 1),
+		],
+	));
+	ops
 }
 
 fn build_sub_in_place(
@@ -1607,6 +2017,37 @@ fn build_nop_move(
 		.collect()
 }
 
+// a cell only keeps the low byte so sign extending is a move, except out of
+// an i1 where true is all ones
+fn build_sext(
+	ctx: &mut Ctx,
+	i: &llvm_ir::Instruction,
+	block: &llvm_ir::BasicBlock,
+	args: &[BuilderArgs],
+	ret: Option<Addr>,
+) -> Vec<BfOp> {
+	let sext: llvm_ir::instruction::SExt = i.clone().try_into().unwrap();
+	let bit = match &sext.operand {
+		llvm_ir::Operand::LocalOperand { ty, .. } => match ty.deref() {
+			llvm_ir::Type::IntegerType { bits } => *bits == 1,
+			_ => false,
+		},
+		_ => false,
+	};
+	if !bit {
+		return build_nop_move(ctx, i, block, args, ret);
+	}
+
+	let dest = ret.unwrap();
+	let (op0, o0) = builder_args_to_consumable_reg(ctx, &args[0]);
+	o0.into_iter()
+		.chain(vec![BfOp::Loop(
+			op0.clone(),
+			vec![BfOp::SubI(op0, 1), BfOp::AddI(dest, 255)],
+		)])
+		.collect()
+}
+
 fn build_icmp_instr(
 	ctx: &mut Ctx,
 	i: &llvm_ir::Instruction,
@@ -1702,22 +2143,36 @@ fn build_store(
 	args: &[BuilderArgs], // order is [value, address]
 	ret: Option<Addr>,
 ) -> Vec<BfOp> {
-	let (val, mut o0) = builder_args_to_consumable_reg(ctx, &args[0]);
+	let (val, o0) = builder_args_to_consumable_reg(ctx, &args[0]);
 
-	if let BuilderArgs::Alloc(addr) = &args[1] {
-		vec![]
-			.into_iter()
-			.chain(o0)
-			.chain(vec![BfOp::Zero(addr.clone()), BfOp::Mov(val, addr.clone())])
-			.collect()
+	o0.into_iter().chain(store_through(ctx, val, &args[1])).collect()
+}
+
+// `*ptr = val`, no train needed when ptr is one of our allocas
+fn store_through(ctx: &mut Ctx, val: Addr, ptr: &BuilderArgs) -> Vec<BfOp> {
+	if let BuilderArgs::Alloc(addr) = ptr {
+		vec![BfOp::Zero(addr.clone()), BfOp::Mov(val, addr.clone())]
 	} else {
-		let (addr, mut o1) = builder_args_to_consumable_reg(ctx, &args[1]);
+		let (addr, o1) = builder_args_to_consumable_reg(ctx, ptr);
 
-		vec![]
-			.into_iter()
-			.chain(o0)
-			.chain(o1)
-			.chain(build_ptr_train(ctx, addr.clone(), Some(val.clone()), None))
+		o1.into_iter()
+			.chain(build_ptr_train(ctx, addr, Some(val), None))
+			.collect()
+	}
+}
+
+// `dest = *ptr`, dest has to start out 0
+fn load_through(ctx: &mut Ctx, ptr: &BuilderArgs, dest: Addr) -> Vec<BfOp> {
+	if let BuilderArgs::Alloc(addr) = ptr {
+		let tmp = borrow_reg(ctx, 1);
+		vec![
+			BfOp::Dup(addr.clone(), dest, tmp.clone()),
+			BfOp::Mov(tmp.clone(), addr.clone()),
+		]
+	} else {
+		let (addr, o) = builder_args_to_consumable_reg(ctx, ptr);
+		o.into_iter()
+			.chain(build_ptr_train(ctx, addr, None, Some(dest)))
 			.collect()
 	}
 }
@@ -1772,20 +2227,50 @@ fn build_load(
 	args: &[BuilderArgs],
 	ret: Option<Addr>,
 ) -> Vec<BfOp> {
-	if let BuilderArgs::Alloc(addr) = &args[0] {
-		let tmp = borrow_reg(ctx, 1);
-		vec![
-			BfOp::Dup(addr.clone(), ret.unwrap(), tmp.clone()),
-			BfOp::Mov(tmp.clone(), addr.clone()),
-		]
-	} else {
-		let (addr, o) = builder_args_to_consumable_reg(ctx, &args[0]);
-		vec![]
-			.into_iter()
-			.chain(o)
-			.chain(build_ptr_train(ctx, addr, None, Some(ret.unwrap())))
-			.collect()
-	}
+	load_through(ctx, &args[0], ret.unwrap())
+}
+
+// A va_list is just a pointer to the next vararg cell, so va_arg reads
+// through it and bumps it along. Every type is a cell so it's always by 1.
+fn build_va_arg(
+	ctx: &mut Ctx,
+	i: &llvm_ir::Instruction,
+	block: &llvm_ir::BasicBlock,
+	args: &[BuilderArgs],
+	ret: Option<Addr>,
+) -> Vec<BfOp> {
+	let mut ops = vec![];
+
+	// need the va_list's address twice, once to read and once to write back
+	let (ap_load, ap_store) = match &args[0] {
+		BuilderArgs::Alloc(a) => {
+			(BuilderArgs::Alloc(a.clone()), BuilderArgs::Alloc(a.clone()))
+		}
+		ap => {
+			let (a, mut o) = builder_args_to_consumable_reg(ctx, ap);
+			let a0 = borrow_reg(ctx, 1);
+			let a1 = borrow_reg(ctx, 1);
+			ops.append(&mut o);
+			ops.push(BfOp::Dup(a, a0.clone(), a1.clone()));
+			(BuilderArgs::ConsumedReg(a0), BuilderArgs::ConsumedReg(a1))
+		}
+	};
+
+	let cur = borrow_reg(ctx, 1);
+	let here = borrow_reg(ctx, 1);
+	let next = borrow_reg(ctx, 1);
+	ops.push(BfOp::Tag(cur.clone(), "va_cur".to_string()));
+	ops.append(&mut load_through(ctx, &ap_load, cur.clone()));
+	ops.push(BfOp::Dup(cur, here.clone(), next.clone()));
+	ops.append(&mut load_through(
+		ctx,
+		&BuilderArgs::ConsumedReg(here),
+		ret.unwrap(),
+	));
+	ops.push(BfOp::AddI(next.clone(), 1));
+	ops.append(&mut store_through(ctx, next, &ap_store));
+
+	ops
 }
 
 fn build_call(
@@ -1795,63 +2280,63 @@ fn build_call(
 	args: &[BuilderArgs],
 	ret: Option<Addr>,
 ) -> Vec<BfOp> {
-	assert!(ret.is_none());
 	let c = match i {
 		llvm_ir::Instruction::Call(c) => c,
 		_ => panic!("ohnoonono"),
 	};
 
-	let br = match &block.term {
-		llvm_ir::Terminator::Br(br) => &br.dest,
-		_ => unreachable!("terminator of call block must be branch"),
-	};
+	let callee_name = callee_name(c).unwrap();
 
-	let callee_name =
-		match c.function.as_ref().unwrap_right().as_constant().unwrap() {
-			llvm_ir::Constant::GlobalReference { name, .. } => n2nam(&name),
+	let mut callops = vec![];
 
-			_ => unimplemented!(
-				"ohnoes wtf?? {:?}",
-				c.function.as_ref().unwrap_right().as_constant().unwrap()
-			),
-		};
+	// intrinsics stay put in the middle of their block, the rest of the block
+	// and its terminator carry on like normal right after
+	if callee_name.starts_with("llvm.lifetime") {
+		callops.push(BfOp::Comment(format!("ignoring llvm intrinsic")));
+		return callops;
+	}
 
-	// TODO(turbio): even an instric call will end in a
-	// branch lol that could be a lil better
+	// va_lists are a single pointer to the next vararg, see build_va_arg
+	if callee_name == "llvm.va_start" {
+		// no VarArg cells means nobody ever passes us extras, there's nothing
+		// va_arg could read anyways
+		if let Some(first) =
+			ctx.layout.iter().position(|c| c == &Cell::VarArg(0))
+		{
+			let (first, mut o) = builder_args_to_consumable_reg(
+				ctx,
+				&BuilderArgs::Alloc(fixed_addr(first)),
+			);
+			callops.append(&mut o);
+			callops.append(&mut store_through(ctx, first, &args[0]));
+		}
+		return callops;
+	}
 
-	// after the call returns branch to this block. Earlier
-	// we made sure all calls are at the end of a block
-	// which always ends in an unconditional branch.
-	let brto = ctx
-		.layout
-		.iter()
-		.position(|c| match c {
-			Cell::BlockMask(n) => n == br,
-			_ => false,
-		})
-		.unwrap();
-
-	let mut callops = vec![];
-
-	let ret_pad_width = ctx.ret_pad_width.unwrap();
-	let stack_width = ctx.stack_width.unwrap();
-
-	let retpad_addr = ctx.retpad_addr.clone().unwrap();
-	let entry_block_addr = ctx.entry_block_addr.unwrap();
-
-	let own_func_name = "caller";
+	if callee_name == "llvm.va_copy" {
+		let tmp = borrow_reg(ctx, 1);
+		callops.append(&mut load_through(ctx, &args[1], tmp.clone()));
+		callops.append(&mut store_through(ctx, tmp, &args[0]));
+		return callops;
+	}
 
-	callops.push(BfOp::Comment(
-		"enable next block when we return".to_string(),
-	));
-	callops.push(BfOp::Tag(
-		fixed_addr(brto),
-		format!("{}/{}", own_func_name, br),
-	));
-	callops.push(BfOp::AddI(fixed_addr(brto), 1));
+	if callee_name == "llvm.va_end" {
+		callops.push(BfOp::Comment(format!("va_end does nothing")));
+		return callops;
+	}
 
-	if callee_name.starts_with("llvm.lifetime") {
-		callops.push(BfOp::Comment(format!("ignoring llvm intrinsic")));
+	// EOF reads as 0 whether the interpreter writes a 0 or leaves the cell
+	// alone, so long as the cell starts out 0
+	if callee_name == "getchar" {
+		callops.push(BfOp::Comment("getchar intrinsic".to_string()));
+		match ret {
+			Some(dest) => callops.push(BfOp::Getch(dest)),
+			None => {
+				let tmp = borrow_reg(ctx, 1);
+				callops.push(BfOp::Getch(tmp.clone()));
+				callops.push(BfOp::Zero(tmp));
+			}
+		}
 		return callops;
 	}
 
@@ -1888,37 +2373,60 @@ fn build_call(
 		return callops;
 	}
 
-	callops.push(BfOp::Comment(format!("stack_width {}", stack_width)));
+	// after the call returns branch to this block. Earlier
+	// we made sure all calls are at the end of a block
+	// which always ends in an unconditional branch.
+	let br = match &block.term {
+		llvm_ir::Terminator::Br(br) => &br.dest,
+		_ => unreachable!("terminator of call block must be branch"),
+	};
+
+	let ret_pad_width = ctx.ret_pad_width.unwrap();
+	let jump_width = ctx.jump_width(&callee_name);
+	let frame_end = ctx.frame_end.clone().unwrap();
+
+	let retpad_addr = ctx.retpad_addr.clone().unwrap();
+	let entry_block_addr = ctx.entry_block_addr.unwrap();
+
+	let own_func_name = "caller";
+
+	callops.push(BfOp::Comment(
+		"enable next block when we return".to_string(),
+	));
+	callops.append(&mut goto_block(
+		ctx,
+		br,
+		Some(format!("{}/{}", own_func_name, br)),
+	));
+
 	callops.push(BfOp::Comment(format!("ret_pad_width {}", ret_pad_width)));
 
 	for (i, ar) in args.iter().enumerate() {
 		callops.push(BfOp::Comment(format!("copy up arg {}", i)));
 
-		let arg_at =
-			stack_width + ret_pad_width + 1 + (c.arguments.len() - 1 - i);
+		// backwards from the stack pointer, unused slots stay on the left
+		let arg_at = offset(
+			frame_end.clone(),
+			(jump_width - STACK_PTR_W - 1 - i) as i64,
+		);
 
-		callops.push(BfOp::Tag(fixed_addr(arg_at), format!("arg_{}", i)));
+		callops.push(BfOp::Tag(arg_at.clone(), format!("arg_{}", i)));
 
 		let (ar, mut o) = builder_args_to_consumable_reg(ctx, &ar);
 		callops.append(&mut o);
 
-		// TODO(turbio): copy up those args yikers
-		callops.push(BfOp::Mov(ar, fixed_addr(arg_at)));
+		callops.push(BfOp::Mov(ar, arg_at));
 	}
 
 	// stack pointer always goes right before the main
 	// loop and after the args
 	// copy our stack ptr into the callee's plus our
 	// frame size
-	let callee_st_ptr =
-		fixed_addr(stack_width + ret_pad_width + 1 + c.arguments.len());
+	let callee_st_ptr = offset(frame_end.clone(), (jump_width - 1) as i64);
 
 	callops.push(BfOp::Comment(format!("give callee a stack pointer")));
 	callops.push(BfOp::Tag(callee_st_ptr.clone(), format!("stack_ptr")));
-	callops.push(BfOp::AddI(
-		callee_st_ptr.clone(),
-		(stack_width + ret_pad_width + 3) as u8,
-	));
+	callops.push(BfOp::Frame(FrameOp::AddI(callee_st_ptr.clone(), jump_width)));
 	callops.push(BfOp::Left(1)); // forbidden territory
 	callops.push(BfOp::Dup(
 		fixed_addr(0),
@@ -1930,7 +2438,7 @@ fn build_call(
 
 	// setup the jump pad
 
-	callops.push(BfOp::Right(stack_width));
+	callops.push(BfOp::Frame(FrameOp::Right));
 
 	callops.push(BfOp::Tag(fixed_addr(0), format!("JUMP_PAD")));
 	callops.push(BfOp::AddI(fixed_addr(0), 1));
@@ -1949,9 +2457,7 @@ fn build_call(
 
 	// move to callee's frame loc
 
-	callops.push(BfOp::Right(
-		c.arguments.len() + ret_pad_width + 1 + STACK_PTR_W,
-	));
+	callops.push(BfOp::Right(jump_width));
 
 	// setup the callee's frame
 
@@ -1981,9 +2487,82 @@ fn build_call(
 	));
 	callops.push(BfOp::AddI(fixed_addr(entry_block_addr), 1));
 
+	if ctx.stack_guard {
+		return stack_guard(ctx, &callee_name, callops);
+	}
+
 	callops
 }
 
+// marks the jump pad of a frame that tried to call with no room left
+pub const STACK_OVERFLOW_MARK: u8 = 255;
+
+// Only make the call if the callee's frame fits in what's left of the tape.
+// Otherwise stop the whole machine right here: clear our main loop bit and
+// func mask so every loop falls through, and leave STACK_OVERFLOW_MARK in the
+// first cell past our frame. Everything below it is left as it was for
+// whoever's reading the dump.
+fn stack_guard(ctx: &mut Ctx, callee: &str, call: Vec<BfOp>) -> Vec<BfOp> {
+	let jump_width = ctx.jump_width(callee);
+	let frame_end = ctx.frame_end.clone().unwrap();
+	let ownfid = ctx.ownfid.unwrap();
+
+	let sp = borrow_reg(ctx, 1);
+	let budget = borrow_reg(ctx, 1);
+	let over = borrow_reg(ctx, 1);
+	// the call leaves us in the callee's frame where this cell is still 0
+	let fits = borrow_reg(ctx, 1);
+
+	let mut guard = vec![
+		BfOp::Comment(format!("stack guard for {}", callee)),
+		BfOp::Tag(sp.clone(), "guard_sp".to_string()),
+		BfOp::Tag(budget.clone(), "guard_budget".to_string()),
+		BfOp::Left(1),
+		BfOp::Dup(
+			fixed_addr(0),
+			offset(sp.clone(), 1),
+			offset(budget.clone(), 1),
+		),
+		BfOp::Mov(offset(budget.clone(), 1), fixed_addr(0)),
+		BfOp::Right(1),
+		BfOp::Frame(FrameOp::Budget(
+			budget.clone(),
+			callee.to_string(),
+			jump_width,
+		)),
+	];
+	guard.append(&mut build_icmp(
+		ctx,
+		llvm_ir::IntPredicate::UGT,
+		sp,
+		budget,
+		over.clone(),
+	));
+
+	guard.push(BfOp::AddI(fits.clone(), 1));
+	guard.push(BfOp::Loop(
+		over.clone(),
+		vec![
+			BfOp::SubI(over.clone(), 1),
+			BfOp::SubI(fits.clone(), 1),
+			BfOp::Comment("out of stack".to_string()),
+			BfOp::SubI(fixed_addr(0), 1),
+			BfOp::SubI(fixed_addr(ownfid), 1),
+			BfOp::Tag(frame_end.clone(), "STACK_OVERFLOW".to_string()),
+			BfOp::AddI(frame_end, STACK_OVERFLOW_MARK),
+		],
+	));
+	guard.push(BfOp::Loop(
+		fits.clone(),
+		vec![BfOp::SubI(fits.clone(), 1)]
+			.into_iter()
+			.chain(call)
+			.collect(),
+	));
+
+	guard
+}
+
 fn instr_consumes<'i>(
 	ctx: &Ctx,
 	i: &'i llvm_ir::Instruction,
@@ -2008,6 +2587,68 @@ fn instr_consumes<'i>(
 		.collect()
 }
 
+// Values only ever read further down the block that makes them, and the
+// instruction that reads them last. Those only need a cell in between so
+// they can take turns with each other. Anything read by a phi, a terminator
+// or a call that leaves the frame keeps its own cell for the whole call.
+fn block_locals<'f>(
+	ctx: &Ctx,
+	func: &'f llvm_ir::Function,
+) -> HashMap<&'f llvm_ir::Name, usize> {
+	let mut defs = HashMap::new();
+	let mut uses: HashMap<&llvm_ir::Name, Vec<(usize, usize)>> = HashMap::new();
+	let mut escapes = vec![];
+
+	for (b, block) in func.basic_blocks.iter().enumerate() {
+		for (iid, instr) in block.instrs.iter().enumerate() {
+			let opers = match instr {
+				llvm_ir::Instruction::Alloca(_) => continue,
+				_ => instr_opers(ctx, instr),
+			};
+			let names = opers.into_iter().filter_map(|o| match o {
+				llvm_ir::Operand::LocalOperand { name, .. } => Some(name),
+				_ => None,
+			});
+
+			match instr {
+				llvm_ir::Instruction::Phi(_) => escapes.extend(names),
+				_ if leaves_frame(instr) => escapes.extend(names),
+				_ => {
+					for n in names {
+						uses.entry(n).or_default().push((b, iid));
+					}
+					if let Some(r) = instr.try_get_result() {
+						defs.insert(r, b);
+					}
+				}
+			}
+		}
+
+		match &block.term {
+			llvm_ir::Terminator::CondBr(llvm_ir::terminator::CondBr {
+				condition: llvm_ir::Operand::LocalOperand { name, .. },
+				..
+			})
+			| llvm_ir::Terminator::Ret(llvm_ir::terminator::Ret {
+				return_operand: Some(llvm_ir::Operand::LocalOperand { name, .. }),
+				..
+			}) => escapes.push(name),
+			_ => {}
+		}
+	}
+
+	defs.into_iter()
+		.filter(|(n, _)| !escapes.contains(n))
+		.filter_map(|(n, b)| {
+			let at = uses.get(n)?;
+			if at.iter().any(|(ub, _)| *ub != b) {
+				return None;
+			}
+			Some((n, at.iter().map(|(_, iid)| *iid).max()?))
+		})
+		.collect()
+}
+
 fn consumed_op_to_reg(
 	ctx: &mut Ctx,
 	operand: &llvm_ir::Operand,
@@ -2041,6 +2682,54 @@ fn consumed_op_to_reg(
 	}
 }
 
+// Fill in the phis of `to` for coming from `from`. One phi gets its value
+// straight in. With more, one phi's dest can be what another one reads, so
+// everything gets read out first and only then written.
+fn phi_moves(
+	ctx: &mut Ctx,
+	to: &llvm_ir::BasicBlock,
+	from: &llvm_ir::Name,
+	multi_use: &Vec<&llvm_ir::Name>,
+) -> Vec<BfOp> {
+	let phis: Vec<_> = to
+		.instrs
+		.iter()
+		.filter_map(|i| llvm_ir::instruction::Phi::try_from(i.clone()).ok())
+		.collect();
+
+	let mut ops = vec![];
+	let mut vals = vec![];
+	for phi in phis.iter() {
+		let (incoming, _) = phi
+			.incoming_values
+			.iter()
+			.find(|pair| &pair.1 == from)
+			.unwrap();
+
+		ops.push(BfOp::Comment(format!("doing phi stuff")));
+		ops.push(BfOp::Comment(format!("{}", phi)));
+
+		let (v, mut o) = consumed_op_to_reg(ctx, incoming, multi_use);
+		ops.append(&mut o);
+		if phis.len() == 1 {
+			vals.push(v);
+			continue;
+		}
+
+		let staged = borrow_reg(ctx, 1);
+		ops.push(BfOp::Mov(v, staged.clone()));
+		vals.push(staged);
+	}
+
+	for (phi, v) in phis.iter().zip(vals) {
+		let dest = take_reg(ctx, &phi.dest);
+		ops.push(BfOp::Zero(dest.clone()));
+		ops.push(BfOp::Mov(v, dest));
+	}
+
+	ops
+}
+
 fn instr_opers<'i>(
 	ctx: &Ctx,
 	i: &'i llvm_ir::Instruction,
@@ -2068,6 +2757,7 @@ fn instr_opers<'i>(
 		llvm_ir::Instruction::UDiv(i) => vec![&i.operand0, &i.operand1],
 		llvm_ir::Instruction::Mul(i) => vec![&i.operand0, &i.operand1],
 		llvm_ir::Instruction::BitCast(i) => vec![&i.operand],
+		llvm_ir::Instruction::VAArg(i) => vec![&i.arg_list],
 		llvm_ir::Instruction::GetElementPtr(i) => {
 			assert!(i.indices.len() == 1);
 
@@ -2146,18 +2836,23 @@ fn lookup_instr(i: &llvm_ir::Instruction) -> &'static InstrMeta<'static> {
 		llvm_ir::Instruction::GetElementPtr(_) => &InstrMeta {
 			builders: &[(RetMeta::Addr, build_getelemptr)],
 		},
+		llvm_ir::Instruction::VAArg(_) => &InstrMeta {
+			builders: &[(RetMeta::Addr, build_va_arg)],
+		},
 		llvm_ir::Instruction::ZExt(_)
 		| llvm_ir::Instruction::IntToPtr(_)
 		| llvm_ir::Instruction::BitCast(_)
 		| llvm_ir::Instruction::PtrToInt(_)
-		| llvm_ir::Instruction::Trunc(_)
-		| llvm_ir::Instruction::SExt(_) => &InstrMeta {
+		| llvm_ir::Instruction::Trunc(_) => &InstrMeta {
 			builders: &[
 				(RetMeta::Addr, build_nop_move),
 				//(&[ArgsMeta::InPlaceReg], RetMeta::InPlace, build_nop),
 				//(&[ArgsMeta::Const], RetMeta::Addr, build_nop),
 			],
 		},
+		llvm_ir::Instruction::SExt(_) => &InstrMeta {
+			builders: &[(RetMeta::Addr, build_sext)],
+		},
 		_ => unimplemented!("lookup for {}", i),
 	}
 }
@@ -2166,25 +2861,45 @@ fn build_func(
 	globals: &Vec<GlobalMap>,
 	playout: &Layout,
 	ret_pad_width: usize,
-	stack_width: usize,
+	arg_widths: &[(String, usize)],
+	opts: &Options,
 	func: &llvm_ir::Function,
-) -> (Vec<BfOp>, usize) {
-	// returns the stack width too
+) -> (Vec<BfOp>, Layout) {
+	// returns the frame layout too, its length being the stack width
 	let ret_landing_pad =
 		llvm_ir::Name::Name(Box::new("ret_lading_pad".to_string()));
 
+	let frame_end = Addr {
+		v: Rc::new(RefCell::new(Addrt::Named(llvm_ir::Name::Name(
+			Box::new("frame_end".to_string()),
+		)))),
+	};
+
 	let mut ctx = Ctx {
 		layout: playout.clone(),
 		addrs: Vec::<Addr>::new(),
 		ret_pad_width: Some(ret_pad_width),
-		stack_width: Some(stack_width),
+		arg_widths: arg_widths.to_vec(),
+		frame_end: Some(frame_end.clone()),
 		entry_block_addr: None,
 		retpad_addr: None,
 		ownfid: None,
 		globals: globals.clone(),
+		stack_guard: opts.stack_guard,
+	};
+
+	// Order only decides where the masks go and the order blocks get checked
+	// in. Registers still get handed out in source order, moving those around
+	// shifts the seeks in hot loops way more than fallthrough ever saves.
+	let blocks: Vec<&llvm_ir::BasicBlock> = match opts.opt {
+		0 => func.basic_blocks.iter().collect(),
+		_ => block_order(func)
+			.iter()
+			.map(|&b| &func.basic_blocks[b])
+			.collect(),
 	};
 
-	for (i, block) in func.basic_blocks.iter().enumerate() {
+	for (i, block) in blocks.iter().enumerate() {
 		if i == 1 {
 			// block at index 1 is always reserved for the ret landing pad
 			ctx.layout.push(Cell::BlockMask(ret_landing_pad.clone()));
@@ -2210,31 +2925,50 @@ fn build_func(
 		}
 	}
 
+	// we can't know how many extra args we got so make room for as many as
+	// the biggest call hands us
+	let nvarargs = match func.is_var_arg {
+		true => ctx.arg_width(&func.name) - func.parameters.len(),
+		false => 0,
+	};
+	for i in 0..nvarargs {
+		ctx.layout.push(Cell::VarArg(i));
+	}
+
 	let mut name_uses: Vec<&llvm_ir::Name> = vec![];
 	let mut multi_use = vec![];
 	for block in func.basic_blocks.iter() {
-		for instr in block.instrs.iter() {
-			let uses = instr_consumes(&ctx, instr);
-			for u in uses {
-				if name_uses.contains(&u) && !multi_use.contains(&u) {
-					multi_use.push(&u);
-					continue;
-				}
-
-				name_uses.push(u);
+		let mut uses: Vec<&llvm_ir::Name> = block
+			.instrs
+			.iter()
+			.flat_map(|instr| instr_consumes(&ctx, instr))
+			.collect();
+		// a conditional branch eats its condition too
+		if let llvm_ir::Terminator::CondBr(cbr) = &block.term {
+			if let llvm_ir::Operand::LocalOperand { name, .. } = &cbr.condition {
+				uses.push(name);
+			}
+		}
+		for u in uses {
+			if name_uses.contains(&u) && !multi_use.contains(&u) {
+				multi_use.push(&u);
+				continue;
 			}
+
+			name_uses.push(u);
 		}
 	}
 
-	// and then all those regs (aka not allocas)
-	// TODO: register reuse when single_use
+	// and then all those regs (aka not allocas). Block locals get theirs as
+	// they come up, see block_locals.
+	let locals = block_locals(&ctx, func);
 	for block in func.basic_blocks.iter() {
 		for instr in block.instrs.iter() {
 			match instr {
 				llvm_ir::Instruction::Alloca(_) => {}
 				_ => {
 					let ret = instr.try_get_result();
-					if ret.is_some() {
+					if ret.is_some() && !locals.contains_key(ret.unwrap()) {
 						let ret = ret.unwrap();
 						// TODO this is unsound this can only be false when a
 						// register is ONCE through all flows of execution after
@@ -2268,7 +3002,8 @@ fn build_func(
 	let mut first_block_prelude = Vec::<BfOp>::new();
 	first_block_prelude.push(BfOp::Comment(format!("copy up args")));
 	for (i, p) in func.parameters.iter().enumerate() {
-		let pdest = give_reg(&mut ctx, &p.name, false);
+		// same deal as every other reg, an arg can get used more than once
+		let pdest = give_reg(&mut ctx, &p.name, true);
 		first_block_prelude
 			.push(BfOp::Tag(pdest.clone(), format!("arg_{}", p.name)));
 
@@ -2281,6 +3016,33 @@ fn build_func(
 		first_block_prelude.push(BfOp::Right(i + STACK_PTR_W + 1));
 	}
 
+	// the extras sit right after the fixed args, any slot nobody passed
+	// anything in is just 0
+	for i in 0..nvarargs {
+		let at = func.parameters.len() + i + STACK_PTR_W + 1;
+		let vdest = cell_addr(&ctx, &Cell::VarArg(i));
+
+		first_block_prelude.push(BfOp::Left(at));
+		first_block_prelude
+			.push(BfOp::Mov(fixed_addr(0), offset(vdest, at as i64)));
+		first_block_prelude.push(BfOp::Right(at));
+	}
+
+	// calls always end their block in a br to where we pick back up, which
+	// is where a return value gets moved out from past the callee's frame
+	let ret_conts: Vec<(llvm_ir::Name, llvm_ir::Name, String)> = func
+		.basic_blocks
+		.iter()
+		.filter_map(|b| match (b.instrs.last(), &b.term) {
+			(Some(llvm_ir::Instruction::Call(c)), llvm_ir::Terminator::Br(br))
+				if leaves_frame(b.instrs.last().unwrap()) =>
+			{
+				Some((br.dest.clone(), c.dest.clone()?, callee_name(c)?))
+			}
+			_ => None,
+		})
+		.collect();
+
 	// worth noting everone's ret pad and first block have the same address
 	let retpad_addr = ctx
 		.layout
@@ -2323,7 +3085,7 @@ fn build_func(
 			BfOp::SubI(fn_mask(&mut ctx, &func.name), 1),
 			BfOp::Tag(fixed_addr(0), format!("dead_fn_pad/{}", func.name)),
 			BfOp::SubI(retpad_addr.clone(), 1),
-			BfOp::Left(stack_width),
+			BfOp::Frame(FrameOp::Left),
 		],
 	));
 
@@ -2333,6 +3095,9 @@ fn build_func(
 			Cell::Alloc(n) => {
 				funcloop.push(BfOp::Tag(fixed_addr(i), format!("alloc_{}", n)))
 			}
+			Cell::VarArg(n) => {
+				funcloop.push(BfOp::Tag(fixed_addr(i), format!("vararg_{}", n)))
+			}
 			Cell::FuncMask(n) => {
 				funcloop.push(BfOp::Tag(fixed_addr(i), format!("F:{}", n)))
 			}
@@ -2370,7 +3135,7 @@ fn build_func(
 		}
 	}
 
-	for (i, block) in func.basic_blocks.iter().enumerate() {
+	for (i, block) in blocks.iter().enumerate() {
 		let bid = ctx
 			.layout
 			.iter()
@@ -2389,6 +3154,19 @@ fn build_func(
 			blockloop.append(&mut first_block_prelude);
 		}
 
+		if let Some((_, dest, callee)) =
+			ret_conts.iter().find(|(b, _, _)| b == &block.name)
+		{
+			let dest = take_reg(&mut ctx, dest);
+			blockloop.push(BfOp::Comment(format!("pick up return value")));
+			blockloop.push(BfOp::Zero(dest.clone()));
+			blockloop.push(BfOp::Frame(FrameOp::Returned(
+				offset(frame_end.clone(), ctx.jump_width(callee) as i64),
+				callee.clone(),
+				dest,
+			)));
+		}
+
 		for (iid, instr) in block.instrs.iter().enumerate() {
 			blockloop.push(BfOp::Comment(instr.to_string()));
 
@@ -2465,6 +3243,23 @@ fn build_func(
 
 			let ret = instr.try_get_result();
 
+			// first free cell will do, whoever had it last zeroed it
+			if let Some(r) = ret.filter(|r| locals.contains_key(r)) {
+				let at = ctx.layout.iter().position(|c| match c {
+					Cell::Free => true,
+					_ => false,
+				});
+				let at = at.unwrap_or_else(|| {
+					ctx.layout.push(Cell::Free);
+					ctx.layout.len() - 1
+				});
+				ctx.layout[at] = Cell::Reg {
+					n: r.clone(),
+					multi_use: true,
+				};
+				blockloop.push(BfOp::Tag(fixed_addr(at), instr.to_string()));
+			}
+
 			let retcell =
 				ctx.layout.iter().enumerate().find(|(_, c)| match c {
 					Cell::Reg { n, .. } => Some(n) == ret,
@@ -2501,17 +3296,29 @@ fn build_func(
 					_ => c,
 				})
 				.collect();
+
+			// hand back whatever this was the last one to read, once even if
+			// it read it twice
+			for n in instr_consumes(&ctx, instr) {
+				if locals.get(n) != Some(&iid) {
+					continue;
+				}
+				let at = ctx.layout.iter().position(|c| match c {
+					Cell::Reg { n: r, .. } => r == n,
+					_ => false,
+				});
+				if let Some(at) = at {
+					ctx.layout[at] = Cell::Free;
+					blockloop.push(BfOp::Zero(fixed_addr(at)));
+				}
+			}
 		}
 
 		// if it handled a call we know that: the block ended in a call and
 		// the terminator was a unconditional branch. These are both
 		// rolled into the call instruction builder.
 
-		let last = block.instrs.last();
-		if last.is_none()
-			|| llvm_ir::instruction::Call::try_from(last.unwrap().clone())
-				.is_err()
-		{
+		if !block.instrs.last().map_or(false, leaves_frame) {
 			blockloop.push(BfOp::Comment(block.term.to_string()));
 
 			match &block.term {
@@ -2522,243 +3329,115 @@ fn build_func(
 						.find(|bb| br.dest == bb.name)
 						.unwrap();
 
-					let tophis = toblock
-						.instrs
-						.iter()
-						.filter_map(|i| {
-							llvm_ir::instruction::Phi::try_from((*i).clone())
-								.ok()
-						})
-						.collect::<Vec<_>>();
-
-					if tophis.len() > 1 {
-						panic!("idk how to do multiple phis");
-					}
-
-					if tophis.len() == 1 {
-						let tophis = tophis[0].clone();
-						let our_branch = tophis
-							.incoming_values
-							.iter()
-							.find(|pair| pair.1 == block.name)
-							.unwrap();
-
-						blockloop
-							.push(BfOp::Comment(format!("doing phi stuff")));
-						blockloop.push(BfOp::Comment(format!("{}", tophis)));
-
-						match &our_branch.0 {
-							llvm_ir::Operand::LocalOperand { name, ty } => {
-								if multi_use.contains(&name) {
-									panic!("yeek");
-								}
-							}
-							_ => {}
-						}
-
-						let (brval, mut o) = consumed_op_to_reg(
-							&mut ctx,
-							&our_branch.0,
-							&multi_use,
-						);
-						blockloop.append(&mut o);
-
-						let dest = take_reg(&mut ctx, &tophis.dest);
-						blockloop.push(BfOp::Zero(dest.clone()));
-						blockloop.push(BfOp::Mov(brval, dest.clone()));
-					}
-
-					let brto = ctx
-						.layout
-						.iter()
-						.position(|c| match c {
-							Cell::BlockMask(n) => n == &br.dest,
-							_ => false,
-						})
-						.unwrap();
-
-					let brto = fixed_addr(brto);
+					blockloop.append(&mut phi_moves(
+						&mut ctx,
+						toblock,
+						&block.name,
+						&multi_use,
+					));
 
-					//blockloop.push(BfOp::Tag(
-					//	brto.clone(),
-					//	format!("B:{}/{}", func.name, br.dest),
-					//));
-					blockloop.push(BfOp::AddI(brto.clone(), 1));
+					blockloop.append(&mut goto_block(&ctx, &br.dest, None));
 				}
 
 				llvm_ir::Terminator::CondBr(cbr) => {
-					let cond = take_reg(&mut ctx, &unlop(&cbr.condition));
-
-					let tru = ctx
-						.layout
+					// the branch eats its condition, which might still be needed
+					let (cond, mut o) =
+						consumed_op_to_reg(&mut ctx, &cbr.condition, &multi_use);
+					blockloop.append(&mut o);
+
+					let mut tru = goto_block(
+						&ctx,
+						&cbr.true_dest,
+						Some(format!(
+							"B:{}/{}_true",
+							func.name,
+							n2usize(&cbr.true_dest)
+						)),
+					);
+					let mut fals = goto_block(
+						&ctx,
+						&cbr.false_dest,
+						Some(format!(
+							"B:{}/{}_false",
+							func.name,
+							n2usize(&cbr.false_dest)
+						)),
+					);
+
+					let totrublock = func
+						.basic_blocks
 						.iter()
-						.position(|c| match c {
-							Cell::BlockMask(n) => n == &cbr.true_dest,
-							_ => false,
-						})
+						.find(|bb| cbr.true_dest == bb.name)
 						.unwrap();
-					let tru = fixed_addr(tru);
-
-					let fals = ctx
-						.layout
+					let tofalsblock = func
+						.basic_blocks
 						.iter()
-						.position(|c| match c {
-							Cell::BlockMask(n) => n == &cbr.false_dest,
-							_ => false,
-						})
+						.find(|bb| cbr.false_dest == bb.name)
 						.unwrap();
-					let fals = fixed_addr(fals);
-
-					{
-						let totrublock = func
-							.basic_blocks
-							.iter()
-							.find(|bb| cbr.true_dest == bb.name)
-							.unwrap();
-
-						let totruphis = totrublock
-							.instrs
-							.iter()
-							.filter_map(|i| {
-								llvm_ir::instruction::Phi::try_from(
-									(*i).clone(),
-								)
-								.ok()
-							})
-							.collect::<Vec<_>>();
-
-						if totruphis.len() > 1 {
-							panic!("idk how to do multiple phis");
-						}
-
-						if totruphis.len() == 1 {
-							let totruphis = totruphis[0].clone();
-							let our_branch = totruphis
-								.incoming_values
-								.iter()
-								.find(|pair| pair.1 == block.name)
-								.unwrap();
-
-							blockloop.push(BfOp::Comment(format!(
-								"doing phi stuff"
-							)));
-							blockloop
-								.push(BfOp::Comment(format!("{}", totruphis)));
-
-							let (brval, mut o) = consumed_op_to_reg(
-								&mut ctx,
-								&our_branch.0,
-								&multi_use,
-							);
-							blockloop.append(&mut o);
-
-							let dest = take_reg(&mut ctx, &totruphis.dest);
-							blockloop.push(BfOp::Zero(dest.clone()));
-							blockloop.push(BfOp::Mov(brval, dest.clone()));
-						}
-					}
-
-					{
-						let tofalsblock = func
-							.basic_blocks
-							.iter()
-							.find(|bb| cbr.false_dest == bb.name)
-							.unwrap();
-
-						let tofalsphis = tofalsblock
-							.instrs
-							.iter()
-							.filter_map(|i| {
-								llvm_ir::instruction::Phi::try_from(
-									(*i).clone(),
-								)
-								.ok()
-							})
-							.collect::<Vec<_>>();
-
-						if tofalsphis.len() > 1 {
-							panic!("idk how to do multiple phis");
-						}
-
-						if tofalsphis.len() == 1 {
-							let tofalsphis = tofalsphis[0].clone();
-							let our_branch = tofalsphis
-								.incoming_values
-								.iter()
-								.find(|pair| pair.1 == block.name)
-								.unwrap();
-
-							blockloop.push(BfOp::Comment(format!(
-								"doing phi stuff"
-							)));
-							blockloop
-								.push(BfOp::Comment(format!("{}", tofalsphis)));
-
-							let (brval, mut o) = consumed_op_to_reg(
-								&mut ctx,
-								&our_branch.0,
-								&multi_use,
-							);
-							blockloop.append(&mut o);
-
-							let dest = take_reg(&mut ctx, &tofalsphis.dest);
-							blockloop.push(BfOp::Zero(dest.clone()));
-							blockloop.push(BfOp::Mov(brval, dest.clone()));
-						}
-					}
-
 					// TODO(turbio): hacky but well we're using the ret pad
 					// block mask as scratch cause like we'll never need it lol.
 					let temp0 = retpad_addr.clone();
 					blockloop.push(BfOp::AddI(temp0.clone(), 1));
 
+					let mut ontru = vec![
+						BfOp::SubI(cond.clone(), 1),
+						BfOp::SubI(temp0.clone(), 1),
+					];
+					// only the side we take gets its phis set, the other
+					// side's block might still read the old values
+					ontru.append(&mut phi_moves(
+						&mut ctx,
+						totrublock,
+						&block.name,
+						&multi_use,
+					));
+					ontru.append(&mut tru);
+
+					let mut onfals = vec![BfOp::SubI(temp0.clone(), 1)];
+					onfals.append(&mut phi_moves(
+						&mut ctx,
+						tofalsblock,
+						&block.name,
+						&multi_use,
+					));
+					onfals.append(&mut fals);
+
 					blockloop.append(&mut vec![
 						// if truethy
-						BfOp::Loop(
-							cond.clone(),
-							vec![
-								BfOp::SubI(cond.clone(), 1),
-								BfOp::SubI(temp0.clone(), 1),
-								BfOp::AddI(tru.clone(), 1),
-								BfOp::Tag(
-									tru.clone(),
-									format!(
-										"B:{}/{}_true",
-										func.name,
-										n2usize(&cbr.true_dest)
-									),
-								),
-							],
-						),
+						BfOp::Loop(cond.clone(), ontru),
 						// if falsey
-						BfOp::Loop(
-							temp0.clone(),
-							vec![
-								BfOp::SubI(temp0.clone(), 1),
-								BfOp::AddI(fals.clone(), 1),
-								BfOp::Tag(
-									fals.clone(),
-									format!(
-										"B:{}/{}_false",
-										func.name,
-										n2usize(&cbr.false_dest)
-									),
-								),
-							],
-						),
+						BfOp::Loop(temp0.clone(), onfals),
 					]);
 				}
 
 				llvm_ir::Terminator::Ret(r) => {
-					if r.return_operand.is_some() {
-						// TODO(turbio): figure out where to put ret args
-						match &r.return_operand.as_ref().unwrap() {
-							llvm_ir::Operand::LocalOperand { name, .. } => {
-								take_reg(&mut ctx, &name);
-								// blockloop.push(BfOp::Mov(0, 0));
+					// nobody's around to pick up whatever the entry returns
+					let op = r
+						.return_operand
+						.as_ref()
+						.filter(|_| func.name != opts.entry)
+						.filter(|op| match op {
+							llvm_ir::Operand::ConstantOperand(_) => {
+								uncop(&ctx, op) as u8 != 0
+							}
+							_ => true,
+						});
+					// The value goes in the first cell past our frame. Once
+					// we're back in the caller's jump pad whatever's left of
+					// our blocks only looks at cells inside our width, and
+					// the caller never looks that far out, so it sits there
+					// until the caller picks it up.
+					if let Some(op) = op {
+						blockloop.push(BfOp::Comment(format!("return value")));
+						match op {
+							llvm_ir::Operand::LocalOperand { .. } => {
+								let (v, mut o) = op_to_reg(&mut ctx, op);
+								blockloop.append(&mut o);
+								blockloop.push(BfOp::Mov(v, frame_end.clone()));
 							}
 							llvm_ir::Operand::ConstantOperand(_) => {
-								// blockloop.push(BfOp::AddI(0, v as u8));
+								let v = uncop(&ctx, op) as u8;
+								blockloop.push(BfOp::AddI(frame_end.clone(), v));
 							}
 
 							_ => unimplemented!("ignoring meta?"),
@@ -2779,12 +3458,7 @@ fn build_func(
 					blockloop.push(BfOp::Zero(fixed_addr(0)));
 					blockloop.push(BfOp::Right(1));
 
-					// TODO(turbio): well relying on fixed arg lengths is prolly
-					// not gonna work out. Prolly aught to copy args right into
-					// the stack and have an entry block lay em out right.
-					blockloop.push(BfOp::Left(
-						func.parameters.len() + ret_pad_width + 1 + STACK_PTR_W,
-					));
+					blockloop.push(BfOp::Left(ctx.jump_width(&func.name)));
 				}
 				_ => unimplemented!("soon? {:?}", block.term),
 			};
@@ -2806,51 +3480,684 @@ fn build_func(
 		}
 	}
 
+	let stack_width = ctx.layout.len();
+	frame_end.v.replace(Addrt::Fixed(stack_width));
+
 	return (
 		vec![
 			BfOp::Tag(fixed_addr(ownfid), func.name.clone()),
-			BfOp::Loop(fixed_addr(ownfid), funcloop),
+			BfOp::Loop(fixed_addr(ownfid), size_frame(funcloop, stack_width)),
 		],
-		ctx.layout.len(),
+		ctx.layout,
 	);
 }
 
-pub fn compile(path: &Path) -> String {
-	const RET_LANDING_PAD: usize = 1;
+// swap out the ops that were waiting on the frame width
+fn size_frame(ops: Vec<BfOp>, width: usize) -> Vec<BfOp> {
+	ops.into_iter()
+		.map(|op| match op {
+			BfOp::Frame(FrameOp::Right) => BfOp::Right(width),
+			BfOp::Frame(FrameOp::Left) => BfOp::Left(width),
+			BfOp::Frame(FrameOp::AddI(a, n)) => BfOp::AddI(a, (width + n) as u8),
+			BfOp::Frame(FrameOp::Budget(a, callee, n)) => {
+				BfOp::Frame(FrameOp::Budget(a, callee, width + n))
+			}
+			BfOp::Loop(a, body) => BfOp::Loop(a, size_frame(body, width)),
+			BfOp::Loop2(a, b, body) => {
+				BfOp::Loop2(a, b, size_frame(body, width))
+			}
+			op => op,
+		})
+		.collect()
+}
 
-	let path = path.canonicalize().unwrap();
-	let mut module = llvm_ir::Module::from_bc_path(path).unwrap();
+// Once every frame's width is known the stack guards can say how far up the
+// stack pointer is allowed to get. The callee's frame and the first cell past
+// it have to fit in the tape, and its stack pointer in a cell. `room` is the
+// whole tape since stack pointers count from the first cell. Return values
+// get picked up from past the callee's frame so those wait on the widths too.
+fn size_budget(
+	ops: Vec<BfOp>,
+	widths: &[(String, usize)],
+	room: usize,
+) -> Vec<BfOp> {
+	ops.into_iter()
+		.map(|op| match op {
+			BfOp::Frame(FrameOp::Budget(a, callee, n)) => {
+				let w = widths.iter().find(|(f, _)| f == &callee).unwrap().1;
+				// 0 means never, the stack pointer is always past the globals
+				// and the root's ret pad
+				let max =
+					room.saturating_sub(w + 2).min(255).saturating_sub(n);
+				BfOp::AddI(a, max as u8)
+			}
+			BfOp::Frame(FrameOp::Returned(a, callee, b)) => {
+				let w = widths.iter().find(|(f, _)| f == &callee).unwrap().1;
+				BfOp::Mov(offset(a, w as i64), b)
+			}
+			BfOp::Loop(a, body) => {
+				BfOp::Loop(a, size_budget(body, widths, room))
+			}
+			BfOp::Loop2(a, b, body) => {
+				BfOp::Loop2(a, b, size_budget(body, widths, room))
+			}
+			op => op,
+		})
+		.collect()
+}
 
-	calls_terminate_blocks(&mut module);
-	calls_never_in_first_block(&mut module);
+// How the finished BfOp tree gets written out. verify only ever wants the
+// default, the rest are for the cli.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+	// one op per line with indentation, op labels, `#` tags and comments. bfdb
+	// leans on the tags to name tape cells so this stays the default.
+	Annotated,
 
-	let funcns = module.functions.len();
+	// nothing but the 8 brainfuck chars, optionally wrapped every n columns
+	Bf { wrap: Option<usize> },
 
-	let mut root: Vec<BfOp> = vec![];
+	// the BfOp tree itself with addresses resolved
+	BfOp,
 
-	let ret_pad_width = 1 + funcns + RET_LANDING_PAD;
+	// every function's frame layout cell by cell
+	Layout,
 
-	let mut layout: Layout = vec![Cell::MainLoop];
+	// sizes and counts, handy for comparing builds
+	Stats,
 
-	let mut ctx = Ctx {
-		// TODO
-		layout: layout.clone(),
-		addrs: Vec::<Addr>::new(),
-		ret_pad_width: None,
-		stack_width: None,
-		retpad_addr: None,
-		entry_block_addr: None,
-		ownfid: None,
-		globals: Vec::<GlobalMap>::new(),
-	};
+	// layouts, widths and every function's resolved BfOp tree for anyone
+	// who'd rather write their own tools than read annotated bf
+	Json,
+}
 
-	let mut global_addr_at: u8 = 0; 
-	// load globals into beginning of address space
-	for g in module.global_vars.iter() {
-		let mut len = 0;
-		match g.initializer.as_ref().unwrap().deref() {
-			llvm_ir::constant::Constant::Array{element_type, elements} => {
-				len = elements.len() as u8;
+impl Emit {
+	#[allow(dead_code)]
+	pub fn from_name(name: &str) -> Option<Emit> {
+		match name {
+			"bf" => Some(Emit::Bf { wrap: None }),
+			"bf-annotated" => Some(Emit::Annotated),
+			"bfop" => Some(Emit::BfOp),
+			"layout" => Some(Emit::Layout),
+			"stats" => Some(Emit::Stats),
+			"json" => Some(Emit::Json),
+			_ => None,
+		}
+	}
+}
+
+// What we know about the runtime the code is headed for. We never wrap cells
+// so the cell width doesn't matter, but the tape sure does.
+#[derive(Debug, Clone)]
+pub struct Target {
+	pub name: &'static str,
+	pub tape_len: usize,
+	// what clang should think it's compiling for when we start from c, verify
+	// picks its own
+	#[allow(dead_code)]
+	pub triple: Option<&'static str>,
+}
+
+// avr is as close as clang gets to our cells: 8 bit chars, 16 bit ints and
+// pointers. Everything still lands in a single cell but at least the ir isn't
+// full of i32s and i64s. Not every clang has avr though, the cli falls back
+// to the host triple when it doesn't. verify builds its cases for the host
+// triple unless a case names one, so host is the well trodden path.
+const CELLISH_TRIPLE: &str = "avr-unknown-unknown";
+
+pub const TARGETS: &[Target] = &[
+	// the 30k cells everyone's interpreter has had since 1993
+	Target {
+		name: "classic",
+		tape_len: 30000,
+		triple: Some(CELLISH_TRIPLE),
+	},
+	// same tape as checkedcc.c
+	Target {
+		name: "checked",
+		tape_len: 10240,
+		triple: Some(CELLISH_TRIPLE),
+	},
+	// whatever clang defaults to, which is what most verify cases build with
+	Target {
+		name: "host",
+		tape_len: 30000,
+		triple: None,
+	},
+];
+
+#[allow(dead_code)]
+pub fn target(name: &str) -> Option<Target> {
+	TARGETS.iter().find(|t| t.name == name).cloned()
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+	pub emit: Emit,
+	// 0 is a straight translation, anything higher lets us tidy up the output
+	pub opt: u8,
+	pub target: Target,
+	// function the top frame starts executing
+	pub entry: String,
+	// halt with STACK_OVERFLOW_MARK instead of running off the tape
+	pub stack_guard: bool,
+	// bitcode that only gets linked in for whatever the inputs call but don't
+	// define themselves, like libc
+	pub libs: Vec<PathBuf>,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options {
+			emit: Emit::Annotated,
+			opt: 0,
+			target: TARGETS[0].clone(),
+			entry: "main".to_string(),
+			stack_guard: false,
+			libs: vec![],
+		}
+	}
+}
+
+// everything codegen figured out about a function
+struct FuncBuild {
+	name: String,
+	layout: Layout,
+	// the jump frame every call to it puts below its frame
+	jump_width: usize,
+	ops: usize,
+	code: Vec<BfOp>,
+	// functions it calls, builtins aside
+	callees: Vec<String>,
+}
+
+struct Program {
+	root: Vec<BfOp>,
+	ret_pad_width: usize,
+	globals_width: usize,
+	funcs: Vec<FuncBuild>,
+}
+
+// the program plus anything worth knowing that didn't stop us building it
+pub struct Compiled {
+	pub code: String,
+	// only the cli prints these
+	#[allow(dead_code)]
+	pub warnings: Vec<String>,
+}
+
+// compile one or more bitcode modules into a single program
+pub fn compile_with(
+	paths: &[PathBuf],
+	opts: &Options,
+) -> Result<Compiled, String> {
+	let prog = build(load(paths, &opts.libs)?, opts)?;
+	let warnings = stack_warnings(&prog, opts);
+
+	Ok(Compiled {
+		code: emit(prog, opts),
+		warnings: warnings,
+	})
+}
+
+// build like compile_with would and then look for anywhere the output could
+// break our promise instead of emitting it
+pub fn check_with(
+	paths: &[PathBuf],
+	opts: &Options,
+) -> Result<Vec<Finding>, String> {
+	let prog = build(load(paths, &opts.libs)?, opts)?;
+
+	Ok(check(&prog, opts))
+}
+
+fn load(
+	paths: &[PathBuf],
+	libs: &[PathBuf],
+) -> Result<llvm_ir::Module, String> {
+	fn read(paths: &[PathBuf]) -> Result<Vec<llvm_ir::Module>, String> {
+		let mut modules = vec![];
+		for path in paths {
+			let path = path
+				.canonicalize()
+				.map_err(|e| format!("{}: {}", path.display(), e))?;
+			modules.push(
+				llvm_ir::Module::from_bc_path(&path)
+					.map_err(|e| format!("{}: {}", path.display(), e))?,
+			);
+		}
+		Ok(modules)
+	}
+
+	link(read(paths)?, read(libs)?)
+}
+
+// calls build_call handles right there in the caller, no function needed
+fn builtin_callee(name: &str) -> bool {
+	name == "putchar"
+		|| name == "getchar"
+		|| name.starts_with("llvm.lifetime")
+		|| name.starts_with("llvm.va_")
+}
+
+// a call that actually jumps off to another function's frame
+fn leaves_frame(i: &llvm_ir::Instruction) -> bool {
+	match i {
+		llvm_ir::Instruction::Call(c) => {
+			!callee_name(c).map_or(false, |n| builtin_callee(&n))
+		}
+		_ => false,
+	}
+}
+
+fn callee_name(c: &llvm_ir::instruction::Call) -> Option<String> {
+	match c.function.as_ref().right()?.as_constant()? {
+		llvm_ir::Constant::GlobalReference { name, .. } => Some(n2nam(name)),
+		_ => None,
+	}
+}
+
+// static and friends, only visible inside the module that defines them
+fn local_linkage(linkage: llvm_ir::module::Linkage) -> bool {
+	match linkage {
+		llvm_ir::module::Linkage::Private | llvm_ir::module::Linkage::Internal => true,
+		_ => false,
+	}
+}
+
+fn rename_constant(
+	c: &llvm_ir::ConstantRef,
+	renames: &HashMap<llvm_ir::Name, llvm_ir::Name>,
+) -> llvm_ir::ConstantRef {
+	use self::llvm_ir::constant::*;
+
+	let each = |cs: &Vec<ConstantRef>| -> Vec<ConstantRef> {
+		cs.iter().map(|c| rename_constant(c, renames)).collect()
+	};
+
+	let renamed = match c.deref() {
+		Constant::GlobalReference { name, ty } => match renames.get(name) {
+			Some(to) => Constant::GlobalReference {
+				name: to.clone(),
+				ty: ty.clone(),
+			},
+			None => return c.clone(),
+		},
+		Constant::GetElementPtr(g) => Constant::GetElementPtr(GetElementPtr {
+			address: rename_constant(&g.address, renames),
+			indices: each(&g.indices),
+			in_bounds: g.in_bounds,
+		}),
+		Constant::BitCast(b) => Constant::BitCast(BitCast {
+			operand: rename_constant(&b.operand, renames),
+			to_type: b.to_type.clone(),
+		}),
+		Constant::PtrToInt(p) => Constant::PtrToInt(PtrToInt {
+			operand: rename_constant(&p.operand, renames),
+			to_type: p.to_type.clone(),
+		}),
+		Constant::IntToPtr(p) => Constant::IntToPtr(IntToPtr {
+			operand: rename_constant(&p.operand, renames),
+			to_type: p.to_type.clone(),
+		}),
+		Constant::Array { element_type, elements } => Constant::Array {
+			element_type: element_type.clone(),
+			elements: each(elements),
+		},
+		Constant::Struct { name, values, is_packed } => Constant::Struct {
+			name: name.clone(),
+			values: each(values),
+			is_packed: *is_packed,
+		},
+		Constant::Vector(v) => Constant::Vector(each(v)),
+		_ => return c.clone(),
+	};
+
+	ConstantRef::new(renamed)
+}
+
+fn rename_operand(
+	op: &mut llvm_ir::Operand,
+	renames: &HashMap<llvm_ir::Name, llvm_ir::Name>,
+) {
+	if let llvm_ir::Operand::ConstantOperand(c) = op {
+		*c = rename_constant(c, renames);
+	}
+}
+
+// Every operand of the instructions we know how to build. A reference hiding
+// in anything else keeps its old name and comes out as an undefined symbol,
+// not as somebody else's.
+fn rename_instr(
+	instr: &mut llvm_ir::Instruction,
+	renames: &HashMap<llvm_ir::Name, llvm_ir::Name>,
+) {
+	use self::llvm_ir::Instruction as I;
+
+	let ops: Vec<&mut llvm_ir::Operand> = match instr {
+		I::Add(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Sub(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Mul(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::UDiv(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::SDiv(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::URem(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::SRem(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::And(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Or(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Xor(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Shl(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::LShr(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::AShr(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::ICmp(i) => vec![&mut i.operand0, &mut i.operand1],
+		I::Trunc(i) => vec![&mut i.operand],
+		I::ZExt(i) => vec![&mut i.operand],
+		I::SExt(i) => vec![&mut i.operand],
+		I::PtrToInt(i) => vec![&mut i.operand],
+		I::IntToPtr(i) => vec![&mut i.operand],
+		I::BitCast(i) => vec![&mut i.operand],
+		I::Load(i) => vec![&mut i.address],
+		I::Store(i) => vec![&mut i.address, &mut i.value],
+		I::GetElementPtr(i) => {
+			let mut ops = vec![&mut i.address];
+			ops.extend(i.indices.iter_mut());
+			ops
+		}
+		I::Phi(i) => i.incoming_values.iter_mut().map(|(o, _)| o).collect(),
+		I::Select(i) => {
+			vec![&mut i.condition, &mut i.true_value, &mut i.false_value]
+		}
+		I::Call(i) => {
+			let mut ops: Vec<&mut llvm_ir::Operand> =
+				i.arguments.iter_mut().map(|(o, _)| o).collect();
+			if let Some(f) = i.function.as_mut().right() {
+				ops.push(f);
+			}
+			ops
+		}
+		I::VAArg(i) => vec![&mut i.arg_list],
+		_ => vec![],
+	};
+
+	for op in ops {
+		rename_operand(op, renames);
+	}
+}
+
+// Private and internal symbols (string literals, statics) belong to the
+// module that has them, so two modules can each have an @.str or a static
+// helper without meaning the same thing. Any such symbol whose name turns up
+// in another module gets a fresh one, references and all, so nothing mixes
+// them up once everything shares a namespace.
+fn rename_locals(modules: &mut [llvm_ir::Module]) {
+	let names_in = |m: &llvm_ir::Module| -> Vec<llvm_ir::Name> {
+		m.functions
+			.iter()
+			.map(|f| llvm_ir::Name::from(f.name.clone()))
+			.chain(m.global_vars.iter().map(|g| g.name.clone()))
+			// whatever it calls without defining
+			.chain(
+				m.functions
+					.iter()
+					.flat_map(|f| f.basic_blocks.iter())
+					.flat_map(|b| b.instrs.iter())
+					.filter_map(|i| match i {
+						llvm_ir::Instruction::Call(c) => callee_name(c),
+						_ => None,
+					})
+					.map(llvm_ir::Name::from),
+			)
+			.collect()
+	};
+	let names: Vec<Vec<llvm_ir::Name>> = modules.iter().map(names_in).collect();
+	let mut taken: Vec<llvm_ir::Name> = names.iter().flatten().cloned().collect();
+
+	for (m, module) in modules.iter_mut().enumerate() {
+		let mut renames: HashMap<llvm_ir::Name, llvm_ir::Name> = HashMap::new();
+		let mut rename = |name: &llvm_ir::Name| -> Option<llvm_ir::Name> {
+			let elsewhere = names
+				.iter()
+				.enumerate()
+				.any(|(other, ns)| other != m && ns.contains(name));
+			if !elsewhere {
+				return None;
+			}
+
+			let base = match name {
+				llvm_ir::Name::Name(n) => n.to_string(),
+				llvm_ir::Name::Number(n) => n.to_string(),
+			};
+			let mut to = llvm_ir::Name::from(format!("{}.{}", base, m));
+			let mut n = 0;
+			while taken.contains(&to) {
+				n += 1;
+				to = llvm_ir::Name::from(format!("{}.{}.{}", base, m, n));
+			}
+
+			taken.push(to.clone());
+			renames.insert(name.clone(), to.clone());
+			Some(to)
+		};
+
+		for f in module.functions.iter_mut() {
+			if local_linkage(f.linkage) {
+				if let Some(to) = rename(&llvm_ir::Name::from(f.name.clone())) {
+					f.name = n2nam(&to);
+				}
+			}
+		}
+		for g in module.global_vars.iter_mut() {
+			if local_linkage(g.linkage) {
+				if let Some(to) = rename(&g.name) {
+					g.name = to;
+				}
+			}
+		}
+
+		if renames.is_empty() {
+			continue;
+		}
+
+		for g in module.global_vars.iter_mut() {
+			if let Some(init) = g.initializer.as_ref() {
+				g.initializer = Some(rename_constant(init, &renames));
+			}
+		}
+		for f in module.functions.iter_mut() {
+			for b in f.basic_blocks.iter_mut() {
+				for i in b.instrs.iter_mut() {
+					rename_instr(i, &renames);
+				}
+				match &mut b.term {
+					llvm_ir::Terminator::Ret(r) => {
+						if let Some(op) = r.return_operand.as_mut() {
+							rename_operand(op, &renames);
+						}
+					}
+					llvm_ir::Terminator::CondBr(c) => {
+						rename_operand(&mut c.condition, &renames)
+					}
+					llvm_ir::Terminator::Switch(s) => {
+						rename_operand(&mut s.operand, &renames)
+					}
+					_ => {}
+				}
+			}
+		}
+	}
+}
+
+// Squish a bunch of modules into one so every function gets a spot in the same
+// FuncMask layout and every global in the same global area. It's basically
+// llvm-link minus everything llvm-link is actually good at.
+//
+// Also the place we make sure every call has something to call. A single
+// module goes through here too so a missing function is a nice error instead
+// of a panic halfway through codegen.
+//
+// Library functions the modules export themselves get dropped so the user's
+// win, anything nothing ends up calling gets pruned along with the rest of the
+// dead code in build. Statics never clash with anything, see rename_locals.
+fn link(
+	modules: Vec<llvm_ir::Module>,
+	libs: Vec<llvm_ir::Module>,
+) -> Result<llvm_ir::Module, String> {
+	if modules.is_empty() {
+		return Err("nothing to compile".to_string());
+	}
+
+	let user_funcs: Vec<String> = modules
+		.iter()
+		.flat_map(|m| m.functions.iter())
+		.filter(|f| !local_linkage(f.linkage))
+		.map(|f| f.name.clone())
+		.collect();
+	let libs = libs.into_iter().map(|mut lib| {
+		lib.functions.retain(|f| {
+			local_linkage(f.linkage) || !user_funcs.contains(&f.name)
+		});
+		lib
+	});
+
+	let mut modules: Vec<llvm_ir::Module> = modules.into_iter().chain(libs).collect();
+	rename_locals(&mut modules);
+
+	let mut modules = modules.into_iter();
+	let mut linked = modules.next().unwrap();
+
+	// who defined what, for the error messages
+	let mut defined_by: Vec<(String, String)> = vec![];
+	let mut check_dup = |name: String, module: &String| -> Result<(), String> {
+		if let Some((_, first)) = defined_by.iter().find(|(n, _)| n == &name) {
+			return Err(format!(
+				"duplicate symbol `{}` defined in both {} and {}",
+				name, first, module
+			));
+		}
+
+		defined_by.push((name, module.clone()));
+		Ok(())
+	};
+
+	// locals have names of their own by now, only exports can clash
+	let exported = |g: &llvm_ir::module::GlobalVariable| {
+		g.initializer.is_some() && !local_linkage(g.linkage)
+	};
+
+	for f in linked.functions.iter().filter(|f| !local_linkage(f.linkage)) {
+		check_dup(f.name.clone(), &linked.name)?;
+	}
+	for g in linked.global_vars.iter().filter(|g| exported(g)) {
+		check_dup(n2nam(&g.name), &linked.name)?;
+	}
+
+	for module in modules {
+		for f in module.functions {
+			if !local_linkage(f.linkage) {
+				check_dup(f.name.clone(), &module.name)?;
+			}
+			linked.functions.push(f);
+		}
+
+		for g in module.global_vars {
+			if exported(&g) {
+				check_dup(n2nam(&g.name), &module.name)?;
+			}
+			linked.global_vars.push(g);
+		}
+	}
+
+	// extern globals just point at a definition somewhere else, which we need
+	// to have found by now
+	let defined_globals = linked
+		.global_vars
+		.iter()
+		.filter(|g| g.initializer.is_some())
+		.map(|g| g.name.clone())
+		.collect::<Vec<_>>();
+
+	for g in linked.global_vars.iter() {
+		if g.initializer.is_none() && !defined_globals.contains(&g.name) {
+			return Err(format!("undefined global `{}`", n2nam(&g.name)));
+		}
+	}
+
+	linked.global_vars.retain(|g| g.initializer.is_some());
+
+	for f in linked.functions.iter() {
+		for i in f.basic_blocks.iter().flat_map(|b| b.instrs.iter()) {
+			let callee = match i {
+				llvm_ir::Instruction::Call(c) => callee_name(c),
+				_ => continue,
+			};
+
+			let callee = callee.ok_or(format!(
+				"`{}` makes an indirect call and we only do direct ones",
+				f.name
+			))?;
+
+			if !builtin_callee(&callee)
+				&& linked.get_func_by_name(&callee).is_none()
+			{
+				return Err(format!(
+					"undefined reference to `{}` in `{}`",
+					callee, f.name
+				));
+			}
+		}
+	}
+
+	Ok(linked)
+}
+
+fn build(mut module: llvm_ir::Module, opts: &Options) -> Result<Program, String> {
+	const RET_LANDING_PAD: usize = 1;
+
+	drop_unreachable_funcs(&mut module, &opts.entry);
+	calls_terminate_blocks(&mut module);
+	calls_never_in_first_block(&mut module);
+
+	let funcns = module.functions.len();
+
+	let mut root: Vec<BfOp> = vec![];
+
+	let ret_pad_width = 1 + funcns + RET_LANDING_PAD;
+	let arg_widths = arg_widths(&module);
+	// the root "calls" the entry so the top frame's jump frame is its
+	let entry_args = arg_widths
+		.iter()
+		.find(|(f, _)| f == &opts.entry)
+		.map_or(0, |(_, n)| *n);
+	let jump_width = jump_frame_width(ret_pad_width, entry_args);
+
+	let mut layout: Layout = vec![Cell::MainLoop];
+
+	let mut ctx = Ctx {
+		// TODO
+		layout: layout.clone(),
+		addrs: Vec::<Addr>::new(),
+		ret_pad_width: Some(ret_pad_width),
+		arg_widths: arg_widths.clone(),
+		frame_end: None,
+		retpad_addr: None,
+		entry_block_addr: None,
+		ownfid: None,
+		globals: Vec::<GlobalMap>::new(),
+		stack_guard: opts.stack_guard,
+	};
+
+	let mut global_addr_at: usize = 0;
+	// nothing goes in cell 0, a pointer to it would be null
+	if !module.global_vars.is_empty() {
+		root.push(BfOp::Right(1));
+		global_addr_at = 1;
+	}
+	// load globals into beginning of address space
+	for g in module.global_vars.iter() {
+		let mut len = 0;
+		match g.initializer.as_ref().unwrap().deref() {
+			llvm_ir::constant::Constant::Array{element_type, elements} => {
+				len = elements.len();
 				for e in elements.iter() {
 					match e.deref() {
 						llvm_ir::constant::Constant::Int{bits, value} => {
@@ -2862,74 +4169,1450 @@ pub fn compile(path: &Path) -> String {
 				}
 			}
 
-			_ => unimplemented!("o {:?}", g.initializer),
+			// char buf[n], the cells are already 0
+			llvm_ir::constant::Constant::AggregateZero(ty) => {
+				len = type_cells(ty) as usize;
+				root.push(BfOp::Right(len));
+			}
+
+			llvm_ir::constant::Constant::Int { value, .. } => {
+				len = 1;
+				root.push(BfOp::AddI(fixed_addr(0), *value as u8));
+				root.push(BfOp::Right(1));
+			}
+
+			_ => unimplemented!("o {:?}", g.initializer),
+		}
+
+		// past 255 the addresses are garbage, but then so is the whole
+		// program and we bail right after the loop
+		ctx.globals.push(GlobalMap{ name: g.name.clone(), addr: global_addr_at as u8 });
+		global_addr_at += len;
+	}
+
+	// Stack pointers count from the first cell so a pointer into the globals
+	// is as good as one into a frame. Every frame's stack pointer has to fit
+	// in a cell.
+	let stack_base = global_addr_at + jump_width - STACK_PTR_W;
+	if stack_base > 255 {
+		return Err(format!(
+			"globals take up {} cells and the jump frame {} more, but \
+			 pointers only reach cell 255",
+			global_addr_at,
+			jump_width - STACK_PTR_W
+		));
+	}
+
+	// the top frame gets a jump frame too, nobody ever sets its pad
+	root.push(BfOp::Right(jump_width - STACK_PTR_W));
+	root.push(BfOp::AddI(fixed_addr(0), stack_base as u8)); // stack base address
+	root.push(BfOp::Right(1));
+	root.push(BfOp::Comment("runtime init:".to_string()));
+	root.push(BfOp::Tag(fixed_addr(0), "===TOP FRAME".to_string()));
+	root.push(BfOp::AddI(fixed_addr(0), 1));
+
+	// stacks frames are laid out as:
+	// <main loop bit> | <func mask> | <block mask> | <registers> | <scratch>
+	// the main loop bit: is always `1` and part of the runtime's flow control
+	// func/block masks: control the current block of execution
+	//
+	// all the allocas live for the lifetime of the function. Sorta are actually
+	// memory addresses but like that's tricky.
+	// let mut allocs: Vec<&llvm_ir::Name> = vec![];
+	//
+	// live registers available on the stack produced by an instruction and
+	// yet to be consumed by a following instruction. Used as a map for
+	// allocations where each cell holds a llvm register name.
+	//
+	// TODO(turbio): for now onstack registers only live between the
+	// instruction producing them and the instruction consuming them.
+	// Multiple uses is unimplemented.
+	for func in module.functions.iter() {
+		layout.push(Cell::FuncMask(func.name.to_string()))
+	}
+
+	let entryfid = layout
+		.iter()
+		.position(|c| match c {
+			Cell::FuncMask(n) => n == &opts.entry,
+			_ => false,
+		})
+		.ok_or(format!("entry function `{}` isn't defined", opts.entry))?;
+
+	root.push(BfOp::Tag(fixed_addr(entryfid), opts.entry.clone()));
+	root.push(BfOp::AddI(fixed_addr(entryfid), 1));
+	root.push(BfOp::Tag(
+		fixed_addr(1 + funcns),
+		format!("{}/b0", opts.entry),
+	));
+	root.push(BfOp::AddI(fixed_addr(1 + funcns), 1));
+
+	let mut mainloop: Vec<BfOp> = vec![];
+	let mut funcs: Vec<FuncBuild> = vec![];
+
+	// ret pad is always the same width with: main loop + function masks +
+	// landing pad mask
+	let ret_pad_width = 1 + funcns + RET_LANDING_PAD;
+
+	for func in module.functions.iter() {
+		let (code, flayout) = build_func(
+			&ctx.globals,
+			&layout,
+			ret_pad_width,
+			&arg_widths,
+			opts,
+			func,
+		);
+
+		funcs.push(FuncBuild {
+			name: func.name.clone(),
+			layout: flayout,
+			jump_width: ctx.jump_width(&func.name),
+			ops: count_ops(&code),
+			code: code,
+			callees: func
+				.basic_blocks
+				.iter()
+				.flat_map(|b| b.instrs.iter())
+				.filter(|i| leaves_frame(i))
+				.filter_map(|i| match i {
+					llvm_ir::Instruction::Call(c) => callee_name(c),
+					_ => None,
+				})
+				.collect(),
+		});
+	}
+
+	let globals_width = global_addr_at;
+
+	// now the stack guards can know how wide their callees turned out
+	let widths: Vec<(String, usize)> = funcs
+		.iter()
+		.map(|f| (f.name.clone(), f.layout.len()))
+		.collect();
+	for f in funcs.iter_mut() {
+		f.code = size_budget(f.code.clone(), &widths, opts.target.tape_len);
+		mainloop.extend(f.code.iter().cloned());
+	}
+
+	root.push(BfOp::Loop(fixed_addr(0), mainloop));
+
+	// The entry's ret leaves us on the root's jump pad, right past the
+	// globals. They're the only thing left to clean up.
+	if globals_width > 0 {
+		root.push(BfOp::Comment("zero globals".to_string()));
+		root.push(BfOp::Left(globals_width));
+		for g in 0..globals_width {
+			root.push(BfOp::Zero(fixed_addr(g)));
+		}
+	}
+
+	// the top frame sits right after the globals and the root's ret pad
+	let entry_width = funcs
+		.iter()
+		.find(|f| f.name == opts.entry)
+		.map_or(0, |f| f.layout.len());
+	// the guard needs one more for its mark
+	let top =
+		globals_width + jump_width + entry_width + opts.stack_guard as usize;
+	if top > opts.target.tape_len {
+		return Err(format!(
+			"`{}` needs {} cells just to start but target `{}` only has {}",
+			opts.entry, top, opts.target.name, opts.target.tape_len
+		));
+	}
+
+	Ok(Program {
+		root: root,
+		ret_pad_width: ret_pad_width,
+		globals_width: globals_width,
+		funcs: funcs,
+	})
+}
+
+// How far up the tape the stack can get starting from the entry function.
+enum StackDepth {
+	// tape used through the end of the deepest frame and the furthest any
+	// stack pointer gets, each with the calls that get there
+	Bounded {
+		cells: usize,
+		path: Vec<String>,
+		sp: usize,
+		sp_path: Vec<String>,
+	},
+	// somewhere along the way it can call back into itself, this is the loop
+	Recursive(Vec<String>),
+}
+
+fn stack_depth(prog: &Program, entry: &str) -> StackDepth {
+	// per function: (furthest frame end, path), (furthest frame start, path)
+	// both measured from the start of the jump frame below the function's own
+	type Deepest = ((usize, Vec<String>), (usize, Vec<String>));
+
+	fn walk(
+		prog: &Program,
+		f: &str,
+		stack: &mut Vec<String>,
+		seen: &mut Vec<(String, Deepest)>,
+	) -> Result<Deepest, Vec<String>> {
+		if let Some((_, d)) = seen.iter().find(|(n, _)| n == f) {
+			return Ok(d.clone());
+		}
+		if let Some(i) = stack.iter().position(|n| n == f) {
+			let mut cycle = stack[i..].to_vec();
+			cycle.push(f.to_string());
+			return Err(cycle);
+		}
+
+		let func = prog.funcs.iter().find(|func| func.name == f).unwrap();
+		let base = func.jump_width;
+		let width = func.layout.len();
+
+		stack.push(f.to_string());
+		// plus the cell past it where a return value goes
+		let mut end = (base + width + 1, vec![f.to_string()]);
+		let mut start = (base, vec![f.to_string()]);
+		for callee in func.callees.iter() {
+			let ((cend, cendp), (cstart, cstartp)) =
+				walk(prog, callee, stack, seen)?;
+
+			if base + width + cend > end.0 {
+				end = (base + width + cend, vec![f.to_string()]);
+				end.1.extend(cendp);
+			}
+			if base + width + cstart > start.0 {
+				start = (base + width + cstart, vec![f.to_string()]);
+				start.1.extend(cstartp);
+			}
+		}
+		stack.pop();
+
+		seen.push((f.to_string(), (end.clone(), start.clone())));
+		Ok((end, start))
+	}
+
+	match walk(prog, entry, &mut vec![], &mut vec![]) {
+		// the root's jump frame starts right past the globals, each frame's
+		// stack pointer being the cell before it
+		Ok(((end, path), (start, sp_path))) => StackDepth::Bounded {
+			cells: prog.globals_width + end,
+			path: path,
+			sp: prog.globals_width + start - 1,
+			sp_path: sp_path,
+		},
+		Err(cycle) => StackDepth::Recursive(cycle),
+	}
+}
+
+// Stack pointers are a single cell so they top out at 255, and the deepest
+// frame has to fit on the tape. Recursion could go any depth so we leave
+// that one to --stack-guard.
+fn stack_warnings(prog: &Program, opts: &Options) -> Vec<String> {
+	let mut warnings = vec![];
+
+	if let StackDepth::Bounded {
+		cells,
+		path,
+		sp,
+		sp_path,
+	} = stack_depth(prog, &opts.entry)
+	{
+		if cells > opts.target.tape_len {
+			warnings.push(format!(
+				"`{}` needs {} cells of tape at its deepest but the budget is {}",
+				path.join(" -> "),
+				cells,
+				opts.target.tape_len
+			));
+		}
+		if sp > 255 {
+			warnings.push(format!(
+				"`{}` takes the stack pointer to {}, past what a cell can hold",
+				sp_path.join(" -> "),
+				sp
+			));
+		}
+	}
+
+	warnings
+}
+
+// the root minus its main loop, which is just every function's code again
+fn root_setup(root: &[BfOp]) -> Vec<BfOp> {
+	root.iter()
+		.filter(|op| match op {
+			BfOp::Loop(..) => false,
+			_ => true,
+		})
+		.cloned()
+		.collect()
+}
+
+fn emit(prog: Program, opts: &Options) -> String {
+	let mut out = String::from("");
+	match opts.emit {
+		Emit::Annotated => printast(&mut out, prog.root),
+		Emit::Bf { wrap } => {
+			printbf(&mut out, prog.root);
+			if opts.opt > 0 {
+				out = peephole(&out);
+			}
+			if let Some(w) = wrap {
+				out = wrapbf(&out, w);
+			}
+		}
+		Emit::BfOp => printops(&mut out, &prog.root, 0),
+		Emit::Layout => {
+			writeln!(out, "ret_pad_width {}", prog.ret_pad_width).unwrap();
+			for f in prog.funcs.iter() {
+				writeln!(
+					out,
+					"{} stack_width {} jump_frame_width {}",
+					f.name,
+					f.layout.len(),
+					f.jump_width
+				)
+				.unwrap();
+				for (i, c) in f.layout.iter().enumerate() {
+					writeln!(out, "    {:4} {}", i, c).unwrap();
+				}
+			}
+		}
+		Emit::Json => {
+			#[derive(Serialize)]
+			struct Dump<'a> {
+				ret_pad_width: usize,
+				globals_width: usize,
+				root: &'a [BfOp],
+				funcs: Vec<FuncDump<'a>>,
+			}
+			#[derive(Serialize)]
+			struct FuncDump<'a> {
+				name: &'a str,
+				stack_width: usize,
+				jump_frame_width: usize,
+				layout: &'a [Cell],
+				code: &'a [BfOp],
+			}
+
+			// the main loop is just every function's code back to back, no
+			// need to say all of that twice
+			let root = &root_setup(&prog.root);
+			let dump = Dump {
+				ret_pad_width: prog.ret_pad_width,
+				globals_width: prog.globals_width,
+				root: root,
+				funcs: prog
+					.funcs
+					.iter()
+					.map(|f| FuncDump {
+						name: &f.name,
+						stack_width: f.layout.len(),
+						jump_frame_width: f.jump_width,
+						layout: &f.layout,
+						code: &f.code,
+					})
+					.collect(),
+			};
+			out = serde_json::to_string_pretty(&dump).unwrap();
+			out.push('\n');
+		}
+		Emit::Stats => {
+			let mut plain = String::from("");
+			printbf(&mut plain, prog.root.clone());
+			let optimized = peephole(&plain);
+
+			writeln!(out, "target {}", opts.target.name).unwrap();
+			writeln!(out, "functions {}", prog.funcs.len()).unwrap();
+			writeln!(out, "globals_width {}", prog.globals_width).unwrap();
+			writeln!(out, "ret_pad_width {}", prog.ret_pad_width).unwrap();
+			writeln!(
+				out,
+				"max_jump_frame_width {}",
+				prog.funcs.iter().map(|f| f.jump_width).max().unwrap_or(0)
+			)
+			.unwrap();
+			writeln!(
+				out,
+				"max_stack_width {}",
+				prog.funcs.iter().map(|f| f.layout.len()).max().unwrap_or(0)
+			)
+			.unwrap();
+			writeln!(out, "tape_len {}", opts.target.tape_len).unwrap();
+			match stack_depth(&prog, &opts.entry) {
+				StackDepth::Bounded {
+					cells,
+					path,
+					sp,
+					sp_path,
+				} => {
+					writeln!(out, "stack_depth {} {}", cells, path.join(" -> "))
+						.unwrap();
+					writeln!(
+						out,
+						"max_stack_ptr {} {}",
+						sp,
+						sp_path.join(" -> ")
+					)
+					.unwrap();
+				}
+				StackDepth::Recursive(cycle) => {
+					writeln!(out, "stack_depth recursive {}", cycle.join(" -> "))
+						.unwrap();
+				}
+			}
+			writeln!(out, "bfops {}", count_ops(&prog.root)).unwrap();
+			writeln!(out, "bf_len {}", plain.len()).unwrap();
+			writeln!(out, "bf_len_opt {}", optimized.len()).unwrap();
+			for f in prog.funcs.iter() {
+				writeln!(
+					out,
+					"fn {} stack_width {} bfops {}",
+					f.name,
+					f.layout.len(),
+					f.ops
+				)
+				.unwrap();
+			}
+		}
+	}
+	out
+}
+
+fn count_ops(ops: &Vec<BfOp>) -> usize {
+	ops.iter()
+		.map(|o| match o {
+			BfOp::Loop(_, inner) | BfOp::Loop2(_, _, inner) => {
+				1 + count_ops(inner)
+			}
+			_ => 1,
+		})
+		.sum()
+}
+
+// Static check of the promise from the README: never seek left of the first
+// cell, never take a cell below 0 and never past 255.
+//
+// Every function is checked on its own, over the code it puts in the main
+// loop. We follow each path with an exact cursor offset and a range per cell,
+// keyed relative to the frame. The calling convention fills in the rest. A
+// fresh frame starts with its masks set and nothing but zeros to its right.
+// Whatever a callee does stays right of our frame, and a ret picks back up in
+// the block the caller set before leaving. So a path that seeks out of the
+// frame at the end of a block is a call or a ret and we stop following it
+// there. The path that comes back in through our jump pad carries on from
+// wherever the calls left off.
+//
+// Loops get split up by the exact value of the cell they test. That's enough
+// to see `[x- y+]` can't push y past 255 without doing real relational stuff.
+//
+// Pointer trains can't be followed. Where they drive to depends on the
+// pointer, so we only know the cells they carry along and that they come
+// back. Anything that might break the promise out there gets reported as
+// unknown rather than proven either way. After a store any alloc could hold
+// anything.
+
+type Range = (u8, u8);
+
+// how many times a loop head can grow before we stop being patient with it
+const WIDEN_AFTER: usize = 4;
+// counters get followed value by value as long as that doesn't mean looking
+// at more than this many partitions across all the loops we're nested in
+const SPLIT_BUDGET: usize = 1024;
+// past this many trips through loop bodies in one function we stop splitting
+// altogether, it's less exact but at least it finishes
+const PASS_BUDGET: usize = 200_000;
+
+#[derive(Debug)]
+pub struct Finding {
+	pub func: String,
+	// the closest BfOp::Comment before the op
+	pub context: String,
+	pub problem: String,
+	// out on a pointer train, where we don't know which cells we're on
+	pub unknown: bool,
+}
+
+impl fmt::Display for Finding {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: `{}`: {}", self.func, self.context, self.problem)?;
+		if self.unknown {
+			write!(f, ", can't tell through a pointer")?;
+		}
+		Ok(())
+	}
+}
+
+// where we were before driving off on a pointer train
+#[derive(Debug, Clone, PartialEq)]
+struct Home {
+	off: i64,
+	cells: BTreeMap<i64, Range>,
+	// the cell right before the train, everything right of it rides along
+	before: i64,
+	stored: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AbsState {
+	// in the caller's frame running our jump pad rather than in our own
+	pad: bool,
+	off: i64,
+	cells: BTreeMap<i64, Range>,
+	// set while out on a train, cells are relative to the train then
+	home: Option<Box<Home>>,
+}
+
+fn frame_default(k: i64) -> Range {
+	// anything right of the frame start is zeroed, left of it could be
+	// anybody's
+	if k >= 0 {
+		(0, 0)
+	} else {
+		(0, 255)
+	}
+}
+
+fn join_cells(
+	to: &mut BTreeMap<i64, Range>,
+	from: &BTreeMap<i64, Range>,
+	dflt: &dyn Fn(i64) -> Range,
+	widen: bool,
+) -> bool {
+	let keys: Vec<i64> = to.keys().chain(from.keys()).cloned().collect();
+	let mut changed = false;
+	for k in keys {
+		let a = *to.get(&k).unwrap_or(&dflt(k));
+		let b = *from.get(&k).unwrap_or(&dflt(k));
+		let mut j = (a.0.min(b.0), a.1.max(b.1));
+		if j != a {
+			if widen {
+				// flags stay flags, anything else could be anything
+				let hi = if j.1 <= 1 { j.1 } else { 255 };
+				j = (if j.0 < a.0 { 0 } else { j.0 }, if j.1 > a.1 { hi } else { j.1 });
+			}
+			to.insert(k, j);
+			changed = true;
+		}
+	}
+	changed
+}
+
+impl AbsState {
+	fn fresh(pad: bool) -> AbsState {
+		AbsState {
+			pad: pad,
+			off: 0,
+			cells: BTreeMap::new(),
+			home: None,
+		}
+	}
+
+	fn get(&self, k: i64) -> Range {
+		match self.cells.get(&k) {
+			Some(r) => *r,
+			None if self.home.is_some() => (0, 255),
+			None => frame_default(k),
+		}
+	}
+
+	fn set(&mut self, k: i64, r: Range) {
+		self.cells.insert(k, r);
+	}
+
+	// the cell `a` lands on with the cursor where this state has it
+	fn at(&self, a: &Addr) -> i64 {
+		self.off + resaddr(a.clone()) as i64
+	}
+
+	// states only ever get joined with others that agree on this
+	fn key(&self) -> (bool, i64, Option<i64>) {
+		(self.pad, self.off, self.home.as_ref().map(|h| h.off))
+	}
+
+	fn join(&mut self, o: &AbsState, widen: bool) -> bool {
+		let mut changed = if self.home.is_some() {
+			join_cells(&mut self.cells, &o.cells, &|_| (0, 255), widen)
+		} else {
+			join_cells(&mut self.cells, &o.cells, &frame_default, widen)
+		};
+		if let (Some(h), Some(oh)) = (self.home.as_mut(), o.home.as_ref()) {
+			changed |= join_cells(&mut h.cells, &oh.cells, &frame_default, widen);
+			if oh.stored && !h.stored {
+				h.stored = true;
+				changed = true;
+			}
+		}
+		changed
+	}
+}
+
+// loop heads, split up by where the cursor is, the value of the cell the loop
+// tests and which blocks are lit up
+struct Heads {
+	states: Vec<(AbsState, usize)>,
+	index: HashMap<((bool, i64, Option<i64>), u8, Vec<Range>), usize>,
+	// counters mostly count down, so going from the top means each value
+	// only gets looked at once. Unsplit counters go last
+	work: BTreeSet<(Reverse<u8>, usize)>,
+	exits: Vec<AbsState>,
+}
+
+fn addrs_in(ops: &[BfOp]) -> Vec<i64> {
+	let mut addrs = vec![];
+	for op in ops {
+		let mut v = match op {
+			BfOp::AddI(a, _)
+			| BfOp::SubI(a, _)
+			| BfOp::Zero(a)
+			| BfOp::Putch(a)
+			| BfOp::Getch(a) => vec![a],
+			BfOp::Mov(a, b) => vec![a, b],
+			BfOp::Dup(a, b, c) => vec![a, b, c],
+			_ => vec![],
+		};
+		addrs.extend(v.drain(..).map(|a| resaddr(a.clone()) as i64));
+	}
+	addrs
+}
+
+// the loop a pointer train drives out on: creeps left a cell at a time
+// dragging its cells along. Everything else that moves the cursor around in a
+// loop stays inside the frame and gets followed like anything else.
+fn train_drive(body: &[BfOp]) -> bool {
+	let mut d = 0;
+	let mut drags = false;
+	for op in body {
+		match op {
+			BfOp::Right(n) => d += *n as i64,
+			BfOp::Left(n) => d -= *n as i64,
+			BfOp::Mov(..) => drags = true,
+			BfOp::Loop(..) | BfOp::Loop2(..) => return false,
+			_ => {}
+		}
+	}
+	d == -1 && drags
+}
+
+struct Checker<'a> {
+	func: String,
+	layout: &'a [Cell],
+	stack_width: i64,
+	// how many cells there are left of our frame
+	room: i64,
+	allocs: Vec<i64>,
+	// where calls pick back up once they return
+	calls: Vec<AbsState>,
+	calls_changed: bool,
+	// masks and the like, see `signature`
+	control: Vec<i64>,
+	// how many partitions the loops we're inside of have going
+	weight: usize,
+	passes: usize,
+	// how many pointer trains deep we are
+	trains: usize,
+	context: String,
+	findings: Vec<Finding>,
+}
+
+impl<'a> Checker<'a> {
+	fn report(&mut self, problem: String) {
+		let dup = self.findings.iter().any(|f| {
+			f.context == self.context && f.problem == problem
+		});
+		if !dup {
+			self.findings.push(Finding {
+				func: self.func.clone(),
+				context: self.context.clone(),
+				problem: problem,
+				unknown: self.trains > 0,
+			});
+		}
+	}
+
+	// calls that leave the same blocks and functions set all pick back up
+	// the same way, fold those together
+	fn add_call(&mut self, back: AbsState) {
+		let sig = self.signature(&back);
+		let same = self.calls.iter().position(|c| self.signature(c) == sig);
+		match same {
+			Some(i) => self.calls_changed |= self.calls[i].join(&back, false),
+			None => {
+				self.calls.push(back);
+				self.calls_changed = true;
+			}
+		}
+	}
+
+	fn name(&self, s: &AbsState, k: i64) -> String {
+		let k = if s.pad { k + self.stack_width } else { k };
+		if self.layout.is_empty() {
+			format!("cell {}", k)
+		} else if k >= 0 && k < self.stack_width {
+			match &self.layout[k as usize] {
+				// borrowed cells are handed back by the time the layout is done
+				Cell::Free => format!("scratch cell {}", k),
+				c => format!("{}", c),
+			}
+		} else if k == -1 {
+			format!("the stack pointer")
+		} else if k < 0 {
+			format!("cell {} left of the frame", -k)
+		} else {
+			format!("cell {} past the frame", k - self.stack_width)
+		}
+	}
+
+	fn add(&mut self, s: &mut AbsState, k: i64, v: Range) {
+		let (lo, hi) = s.get(k);
+		if hi as u16 + v.1 as u16 > 255 {
+			let n = self.name(s, k);
+			self.report(format!("{} could go past 255", n));
+		}
+		let (lo, hi) = (lo.saturating_add(v.0), hi.saturating_add(v.1));
+		if self.is_control(s, k) {
+			// a flag past 1 only comes from a branch condition we couldn't
+			// narrow, which is down already. Letting it grow just repeats
+			// that for every block after it
+			s.set(k, (lo.min(1), hi.min(1)));
+		} else {
+			s.set(k, (lo, hi));
+		}
+	}
+
+	fn is_control(&self, s: &AbsState, k: i64) -> bool {
+		let shift = if s.pad { self.stack_width } else { 0 };
+		s.home.is_none() && self.control.contains(&(k + shift))
+	}
+
+	fn shift(&mut self, s: &mut AbsState, d: i64) {
+		if s.home.is_some() {
+			// the train drags its cells along with it
+			s.cells = s.cells.iter().map(|(k, r)| (k - d, *r)).collect();
+			return;
+		}
+		s.off += d;
+		if s.off < -self.room_for(s) {
+			self.report(format!("could seek left of cell 0"));
+		}
+	}
+
+	fn room_for(&self, s: &AbsState) -> i64 {
+		if s.pad {
+			self.room + self.stack_width
+		} else {
+			self.room
+		}
+	}
+
+	// once the cursor's gone off the tape there's nothing left to follow
+	fn gone(&self, s: &AbsState) -> bool {
+		s.home.is_none() && s.off < -self.room_for(s)
+	}
+
+	fn apply(&mut self, op: &BfOp, s: &mut AbsState) {
+		match op {
+			BfOp::Right(n) => self.shift(s, *n as i64),
+			BfOp::Left(n) => self.shift(s, -(*n as i64)),
+			BfOp::AddI(a, n) => {
+				let k = s.at(a);
+				self.add(s, k, (*n, *n));
+			}
+			BfOp::SubI(a, n) => {
+				let k = s.at(a);
+				let (lo, hi) = s.get(k);
+				if lo < *n {
+					let n = self.name(s, k);
+					self.report(format!("{} could go below 0", n));
+				}
+				s.set(k, (lo.saturating_sub(*n), hi.saturating_sub(*n)));
+			}
+			BfOp::Zero(a) => {
+				let k = s.at(a);
+				s.set(k, (0, 0));
+				// only stores clear a spot out there before unloading
+				if let Some(h) = s.home.as_mut() {
+					h.stored = true;
+				}
+			}
+			BfOp::Mov(a, b) => {
+				let (ka, kb) = (s.at(a), s.at(b));
+				let v = s.get(ka);
+				self.add(s, kb, v);
+				s.set(ka, (0, 0));
+			}
+			BfOp::Dup(a, b, c) => {
+				let (ka, kb, kc) = (s.at(a), s.at(b), s.at(c));
+				let v = s.get(ka);
+				self.add(s, kb, v);
+				self.add(s, kc, v);
+				s.set(ka, (0, 0));
+			}
+			BfOp::Getch(a) => {
+				let k = s.at(a);
+				s.set(k, (0, 255));
+			}
+			_ => {}
+		}
+	}
+
+	fn run(&mut self, ops: &[BfOp], mut states: Vec<AbsState>) -> Vec<AbsState> {
+		for op in ops {
+			if states.is_empty() {
+				break;
+			}
+			states = match op {
+				BfOp::Comment(c) => {
+					self.context = c.clone();
+					states
+				}
+				BfOp::Loop(a, body) => self.run_loop(a, body, states),
+				BfOp::Loop2(a, b, body) => self.run_loop2(a, b, body, states),
+				_ => states
+					.into_iter()
+					.filter_map(|mut s| {
+						self.apply(op, &mut s);
+						if self.gone(&s) {
+							None
+						} else {
+							Some(s)
+						}
+					})
+					.collect(),
+			};
+		}
+		states
+	}
+
+	fn run_loop(
+		&mut self,
+		a: &Addr,
+		body: &[BfOp],
+		states: Vec<AbsState>,
+	) -> Vec<AbsState> {
+		let returning = states.iter().any(|s| s.home.is_some());
+		if !returning && !train_drive(body) {
+			if let Some(out) = self.counted(a, body, &states) {
+				return out;
+			}
+			return self.fixpoint(a, body, states);
+		}
+
+		let touched = addrs_in(body);
+		let states = states
+			.into_iter()
+			.map(|mut s| {
+				if s.home.is_none() {
+					let before = *touched.iter().min().unwrap();
+					let cells =
+						touched.iter().map(|t| (*t, s.get(s.off + t))).collect();
+					s.home = Some(Box::new(Home {
+						off: s.off,
+						cells: std::mem::replace(&mut s.cells, cells),
+						before: before,
+						stored: false,
+					}));
+					s.off = 0;
+				}
+				s
+			})
+			.collect();
+
+		self.trains += 1;
+		let exits = self.fixpoint(a, body, states);
+		self.trains -= 1;
+		if !returning {
+			return exits;
+		}
+
+		// back where we started, bring whatever the train carries home
+		let behind = *touched.iter().max().unwrap();
+		let mut out = vec![];
+		for s in exits {
+			let h = *s.home.clone().unwrap();
+			let mut home = AbsState {
+				pad: s.pad,
+				off: h.off,
+				cells: h.cells,
+				home: None,
+			};
+			for k in (h.before + 1)..behind {
+				home.set(h.off + k, s.get(k));
+			}
+			if h.stored {
+				let shift = if s.pad { self.stack_width } else { 0 };
+				for a in self.allocs.iter() {
+					home.set(a - shift, (0, 255));
+				}
+			}
+			self.merge(&mut out, home);
+		}
+		out
+	}
+
+	// `[x- y+ z-]` and friends: run all at once instead of value by value
+	fn counted(
+		&mut self,
+		a: &Addr,
+		body: &[BfOp],
+		states: &[AbsState],
+	) -> Option<Vec<AbsState>> {
+		let mut steps: Vec<(Addr, i64)> = vec![];
+		for op in body {
+			match op {
+				BfOp::AddI(x, n) => steps.push((x.clone(), *n as i64)),
+				BfOp::SubI(x, n) => steps.push((x.clone(), -(*n as i64))),
+				BfOp::Comment(_) | BfOp::Tag(..) | BfOp::Nop => {}
+				_ => return None,
+			}
+		}
+		let ca = resaddr(a.clone());
+		let on_counter: Vec<i64> = steps
+			.iter()
+			.filter(|(x, _)| resaddr(x.clone()) == ca)
+			.map(|(_, n)| *n)
+			.collect();
+		if on_counter != vec![-1] {
+			return None;
+		}
+		// following a counter value by value keeps branches apart, which
+		// matters when the body sets masks. Only shortcut when we can't afford
+		// to do that anyway
+		if states.iter().any(|s| {
+			let (lo, hi) = s.get(s.at(a));
+			self.splits(lo.max(1), hi)
+		}) {
+			return None;
+		}
+
+		let mut out = vec![];
+		for s in states {
+			let mut s = s.clone();
+			let k = s.at(a);
+			let (lo, hi) = s.get(k);
+			for (x, n) in steps.iter() {
+				let kx = s.at(x);
+				if kx == k {
+					continue;
+				}
+				let (xlo, xhi) = s.get(kx);
+				let (xlo, xhi) = (xlo as i64, xhi as i64);
+				let (most, least) = (lo as i64 * n, hi as i64 * n);
+				let (most, least) = (most.max(least), most.min(least));
+				if xhi + most > 255 {
+					let n = self.name(&s, kx);
+					self.report(format!("{} could go past 255", n));
+				}
+				if xlo + least < 0 {
+					let n = self.name(&s, kx);
+					self.report(format!("{} could go below 0", n));
+				}
+				s.set(
+					kx,
+					((xlo + least).max(0).min(255) as u8, (xhi + most).max(0).min(255) as u8),
+				);
+			}
+			s.set(k, (0, 0));
+			self.merge(&mut out, s);
+		}
+		Some(out)
+	}
+
+	fn fixpoint(
+		&mut self,
+		a: &Addr,
+		body: &[BfOp],
+		states: Vec<AbsState>,
+	) -> Vec<AbsState> {
+		let mut heads = Heads {
+			states: vec![],
+			index: HashMap::new(),
+			work: BTreeSet::new(),
+			exits: vec![],
+		};
+
+		for s in states {
+			self.enter(a, s, &mut heads);
+		}
+		while let Some(&(v, i)) = heads.work.iter().next() {
+			heads.work.remove(&(v, i));
+			let head = heads.states[i].0.clone();
+			let weight = self.weight;
+			self.weight *= heads.states.len();
+			self.passes += 1;
+			let outs = self.run(body, vec![head]);
+			self.weight = weight;
+			for o in outs {
+				let sw = self.stack_width;
+				if o.home.is_some() || (o.off >= 0 && (o.pad || o.off < sw)) {
+					self.enter(a, o, &mut heads);
+				} else if o.pad {
+					// landed back in our frame off a ret
+					for c in self.calls.clone() {
+						self.enter(a, c, &mut heads);
+					}
+				} else if o.off >= sw {
+					// a call, pick back up in this frame once it returns
+					let mut back = o;
+					back.off = 0;
+					back.cells.retain(|k, _| *k < sw);
+					self.add_call(back);
+				}
+				// anything else is a ret out of our frame
+			}
+		}
+		heads.exits
+	}
+
+	fn splits(&self, lo: u8, hi: u8) -> bool {
+		hi >= lo
+			&& (hi - lo) as usize * self.weight < SPLIT_BUDGET
+			&& self.passes < PASS_BUDGET
+	}
+
+	fn enter(&self, a: &Addr, s: AbsState, heads: &mut Heads) {
+		let k = s.at(a);
+		let (lo, hi) = s.get(k);
+		if lo == 0 {
+			let mut e = s.clone();
+			e.set(k, (0, 0));
+			self.merge(&mut heads.exits, e);
+		}
+		if hi == 0 {
+			return;
+		}
+		let lo = lo.max(1);
+		let split: Vec<(u8, Range)> = if self.splits(lo, hi) {
+			(lo..=hi).map(|v| (v, (v, v))).collect()
+		} else {
+			vec![(0, (lo, hi))]
+		};
+		for (v, r) in split {
+			let mut p = s.clone();
+			p.set(k, r);
+			let key = (p.key(), v, self.signature(&p));
+			match heads.index.get(&key) {
+				Some(&i) => {
+					let (h, n) = &mut heads.states[i];
+					if h.join(&p, *n >= WIDEN_AFTER) {
+						*n += 1;
+						heads.work.insert((Reverse(v), i));
+					}
+				}
+				None => {
+					heads.index.insert(key, heads.states.len());
+					heads.work.insert((Reverse(v), heads.states.len()));
+					heads.states.push((p, 0));
+				}
+			}
 		}
+	}
 
-		ctx.globals.push(GlobalMap{ name: g.name.clone(), addr: global_addr_at });
-		global_addr_at += len;
+	// which blocks and functions are lit up. Joining states that disagree on
+	// those would have us running blocks nobody asked for
+	fn signature(&self, s: &AbsState) -> Vec<Range> {
+		let shift = if s.pad { self.stack_width } else { 0 };
+		let cells = match &s.home {
+			Some(h) => &h.cells,
+			None => &s.cells,
+		};
+		self.control
+			.iter()
+			.map(|k| k - shift)
+			.map(|k| *cells.get(&k).unwrap_or(&frame_default(k)))
+			.collect()
 	}
 
-	root.push(BfOp::Right(ret_pad_width + STACK_PTR_W));
-	root.push(BfOp::AddI(
-		fixed_addr(0),
-		ret_pad_width as u8 + STACK_PTR_W as u8,
-	)); // stack base address
-	root.push(BfOp::Right(1));
-	root.push(BfOp::Comment("runtime init:".to_string()));
-	root.push(BfOp::Tag(fixed_addr(0), "===TOP FRAME".to_string()));
-	root.push(BfOp::AddI(fixed_addr(0), 1));
+	fn merge(&self, states: &mut Vec<AbsState>, s: AbsState) {
+		let sig = self.signature(&s);
+		match states
+			.iter_mut()
+			.find(|o| o.key() == s.key() && self.signature(o) == sig)
+		{
+			Some(o) => {
+				o.join(&s, false);
+			}
+			None => states.push(s),
+		}
+	}
 
-	// stacks frames are laid out as:
-	// <main loop bit> | <func mask> | <block mask> | <registers> | <scratch>
-	// the main loop bit: is always `1` and part of the runtime's flow control
-	// func/block masks: control the current block of execution
-	//
-	// all the allocas live for the lifetime of the function. Sorta are actually
-	// memory addresses but like that's tricky.
-	// let mut allocs: Vec<&llvm_ir::Name> = vec![];
-	//
-	// live registers available on the stack produced by an instruction and
-	// yet to be consumed by a following instruction. Used as a map for
-	// allocations where each cell holds a llvm register name.
-	//
-	// TODO(turbio): for now onstack registers only live between the
-	// instruction producing them and the instruction consuming them.
-	// Multiple uses is unimplemented.
-	for func in module.functions.iter() {
-		layout.push(Cell::FuncMask(func.name.to_string()))
+	// statically the cursor comes out on `b`. It only really does if `a` was
+	// set, otherwise it's still on `a` and the next Loop2 better sort it out
+	fn run_loop2(
+		&mut self,
+		a: &Addr,
+		b: &Addr,
+		body: &[BfOp],
+		states: Vec<AbsState>,
+	) -> Vec<AbsState> {
+		let skew = resaddr(a.clone()) as i64 - resaddr(b.clone()) as i64;
+		let mut out = vec![];
+		for s in states {
+			let k = s.at(a);
+			let (lo, hi) = s.get(k);
+			if lo == 0 {
+				let mut e = s.clone();
+				e.set(k, (0, 0));
+				e.off += skew;
+				self.merge(&mut out, e);
+			}
+			if hi == 0 {
+				continue;
+			}
+			let mut p = s;
+			p.set(k, (lo.max(1), hi));
+			for mut o in self.run(body, vec![p]) {
+				let kb = o.at(b);
+				let (blo, bhi) = o.get(kb);
+				if bhi > 0 {
+					self.report(format!("can't follow the cursor around a Loop2"));
+				}
+				if blo == 0 {
+					o.set(kb, (0, 0));
+					self.merge(&mut out, o);
+				}
+			}
+		}
+		out
 	}
+}
 
-	let mainfid = layout
-		.iter()
-		.position(|c| match c {
-			Cell::FuncMask(n) => n == "main",
-			_ => false,
-		})
+fn check(prog: &Program, opts: &Options) -> Vec<Finding> {
+	let rpw = prog.ret_pad_width as i64;
+	let funcns = prog.funcs.len();
+	let mut findings = vec![];
+
+	// the init code before the main loop starts out on the very first cell
+	let mut root = Checker {
+		func: "runtime init".to_string(),
+		layout: &[],
+		stack_width: 0,
+		room: 0,
+		allocs: vec![],
+		calls: vec![],
+		calls_changed: false,
+		control: vec![],
+		weight: 1,
+		passes: 0,
+		trains: 0,
+		context: String::new(),
+		findings: vec![],
+	};
+	root.run(&root_setup(&prog.root), vec![AbsState::fresh(false)]);
+	findings.append(&mut root.findings);
+
+	// how much tape sits left of each function's frame, at the least, and
+	// what it gets for a stack pointer
+	let mut room: HashMap<&str, i64> = HashMap::new();
+	let mut sp: HashMap<&str, (u16, u16)> = HashMap::new();
+	let jump = |f: &str| {
+		prog.funcs.iter().find(|func| func.name == f).unwrap().jump_width as i64
+	};
+	let base = prog.globals_width as i64 + jump(&opts.entry);
+	room.insert(&opts.entry, base);
+	sp.insert(&opts.entry, (base as u16 - 1, base as u16 - 1));
+	let mut work = vec![opts.entry.as_str()];
+	while let Some(name) = work.pop() {
+		let f = prog.funcs.iter().find(|f| f.name == name).unwrap();
+		let (r, (lo, hi)) = (room[name], sp[name]);
+		let sw = f.layout.len() as i64;
+		for callee in f.callees.iter() {
+			let cr = r + sw + jump(callee);
+			let step = (sw + jump(callee)) as u16;
+			let csp = ((lo + step).min(255), (hi + step).min(255));
+			let mut changed = false;
+			if room.get(callee.as_str()).map_or(true, |&o| cr < o) {
+				room.insert(callee, cr);
+				changed = true;
+			}
+			let joined = match sp.get(callee.as_str()) {
+				Some(&(olo, ohi)) => (olo.min(csp.0), ohi.max(csp.1)),
+				None => csp,
+			};
+			if sp.get(callee.as_str()) != Some(&joined) {
+				sp.insert(callee, joined);
+				changed = true;
+			}
+			if changed {
+				work.push(callee);
+			}
+		}
+	}
+
+	for f in prog.funcs.iter() {
+		if !room.contains_key(f.name.as_str()) {
+			continue;
+		}
+		let fid = f
+			.layout
+			.iter()
+			.position(|c| c == &Cell::FuncMask(f.name.clone()))
+			.unwrap() as i64;
+		let allocs = f
+			.layout
+			.iter()
+			.enumerate()
+			.filter(|(_, c)| match c {
+				Cell::Alloc(_) | Cell::VarArg(_) => true,
+				_ => false,
+			})
+			.map(|(i, _)| i as i64)
+			.collect();
+
+		let control = f
+			.layout
+			.iter()
+			.enumerate()
+			.filter(|(_, c)| match c {
+				Cell::MainLoop | Cell::FuncMask(_) | Cell::BlockMask(_) => true,
+				_ => false,
+			})
+			.map(|(i, _)| i as i64)
+			.collect();
+
+		let (lo, hi) = sp[f.name.as_str()];
+		let mut frame = AbsState::fresh(false);
+		frame.set(0, (1, 1));
+		frame.set(fid, (1, 1));
+		frame.set(1 + funcns as i64, (1, 1));
+		frame.set(-1, (lo as u8, hi as u8));
+
+		let mut starts = vec![frame];
+		if !f.callees.is_empty() {
+			// what a call leaves in our jump pad
+			let mut pad = AbsState::fresh(true);
+			pad.set(0, (1, 1));
+			pad.set(fid, (1, 1));
+			pad.set(rpw, (1, 1));
+			starts.push(pad);
+		}
+
+		let mut checker = Checker {
+			func: f.name.clone(),
+			layout: &f.layout,
+			stack_width: f.layout.len() as i64,
+			room: room[f.name.as_str()],
+			allocs: allocs,
+			calls: vec![],
+			calls_changed: false,
+			control: control,
+			weight: 1,
+			passes: 0,
+			trains: 0,
+			context: String::new(),
+			findings: vec![],
+		};
+		// a ret lands wherever any of our calls left off, keep going until
+		// we've seen them all
+		loop {
+			checker.calls_changed = false;
+			checker.run(&f.code, starts.clone());
+			if !checker.calls_changed {
+				break;
+			}
+		}
+		findings.append(&mut checker.findings);
+	}
+
+	findings
+}
+
+// cancel out any `<>`, `><`, `+-` and `-+` pairs. Codegen loves to seek
+// somewhere just to seek right back.
+fn peephole(code: &str) -> String {
+	let mut out: Vec<char> = vec![];
+
+	for c in code.chars() {
+		match (out.last(), c) {
+			(Some('<'), '>')
+			| (Some('>'), '<')
+			| (Some('+'), '-')
+			| (Some('-'), '+') => {
+				out.pop();
+			}
+			_ => out.push(c),
+		}
+	}
+
+	out.into_iter().collect()
+}
+
+// dump the op tree. Addresses are frame relative and already resolved.
+fn printops(out: &mut String, ops: &Vec<BfOp>, depth: usize) {
+	let ind = "    ".repeat(depth);
+
+	for op in ops.iter() {
+		match op {
+			BfOp::Right(n) => writeln!(out, "{}right {}", ind, n),
+			BfOp::Left(n) => writeln!(out, "{}left {}", ind, n),
+			BfOp::AddI(a, n) => {
+				writeln!(out, "{}add {} {}", ind, resaddr(a.clone()), n)
+			}
+			BfOp::SubI(a, n) => {
+				writeln!(out, "{}sub {} {}", ind, resaddr(a.clone()), n)
+			}
+			BfOp::Dup(a, b, c) => writeln!(
+				out,
+				"{}dup {} -> {} {}",
+				ind,
+				resaddr(a.clone()),
+				resaddr(b.clone()),
+				resaddr(c.clone())
+			),
+			BfOp::Mov(a, b) => writeln!(
+				out,
+				"{}mov {} -> {}",
+				ind,
+				resaddr(a.clone()),
+				resaddr(b.clone())
+			),
+			BfOp::Putch(a) => writeln!(out, "{}putch {}", ind, resaddr(a.clone())),
+			BfOp::Getch(a) => writeln!(out, "{}getch {}", ind, resaddr(a.clone())),
+			BfOp::Zero(a) => writeln!(out, "{}zero {}", ind, resaddr(a.clone())),
+			BfOp::Tag(a, t) => {
+				writeln!(out, "{}tag {} {}", ind, resaddr(a.clone()), t)
+			}
+			BfOp::Comment(c) => writeln!(out, "{}; {}", ind, c),
+			BfOp::Nop => Ok(()),
+			BfOp::Frame(_) => unreachable!("frame was never sized"),
+			BfOp::Loop(a, inner) => {
+				writeln!(out, "{}loop {} {{", ind, resaddr(a.clone())).unwrap();
+				printops(out, inner, depth + 1);
+				writeln!(out, "{}}}", ind)
+			}
+			BfOp::Loop2(a, b, inner) => {
+				writeln!(
+					out,
+					"{}loop2 {} {} {{",
+					ind,
+					resaddr(a.clone()),
+					resaddr(b.clone())
+				)
+				.unwrap();
+				printops(out, inner, depth + 1);
+				writeln!(out, "{}}}", ind)
+			}
+		}
 		.unwrap();
+	}
+}
 
-	root.push(BfOp::Tag(fixed_addr(mainfid), "main".to_string()));
-	root.push(BfOp::AddI(fixed_addr(mainfid), 1));
-	root.push(BfOp::Tag(fixed_addr(1 + funcns), "main/b0".to_string()));
-	root.push(BfOp::AddI(fixed_addr(1 + funcns), 1));
+// print only the actual brainfuck. Tags and comments never move the cursor
+// here so skipping them entirely saves all the seeking around they'd do.
+fn printbf(out: &mut String, ast: Vec<BfOp>) {
+	printbfi(out, ast, 0);
+}
 
-	let mut mainloop: Vec<BfOp> = vec![];
+fn printbfi(out: &mut String, ast: Vec<BfOp>, cstart: usize) -> usize {
+	let mut cursor = cstart;
 
-	// ret pad is always the same width with: main loop + function masks +
-	// landing pad mask
-	let ret_pad_width = 1 + funcns + RET_LANDING_PAD;
+	let cmov = |out: &mut String, from: usize, to: usize| {
+		if to > from {
+			out.push_str(&">".repeat(to - from));
+		} else {
+			out.push_str(&"<".repeat(from - to));
+		}
+	};
 
-	for func in module.functions.iter() {
-		let (_, st_width) = build_func(&ctx.globals, &layout, ret_pad_width, 0, func);
-		let (mut code, _) = build_func(&ctx.globals, &layout, ret_pad_width, st_width, func);
+	for ins in ast {
+		match ins {
+			BfOp::Right(n) => out.push_str(&">".repeat(n)),
+			BfOp::Left(n) => out.push_str(&"<".repeat(n)),
+
+			BfOp::Zero(addr) => {
+				cmov(out, cursor, resaddr(addr.clone()));
+				cursor = resaddr(addr);
+				out.push_str("[-]");
+			}
+			BfOp::Putch(addr) => {
+				cmov(out, cursor, resaddr(addr.clone()));
+				cursor = resaddr(addr);
+				out.push('.');
+			}
+			BfOp::Getch(addr) => {
+				cmov(out, cursor, resaddr(addr.clone()));
+				cursor = resaddr(addr);
+				out.push(',');
+			}
+			BfOp::AddI(addr, n) => {
+				cmov(out, cursor, resaddr(addr.clone()));
+				cursor = resaddr(addr);
+				out.push_str(&"+".repeat(n as usize));
+			}
+			BfOp::SubI(addr, n) => {
+				cmov(out, cursor, resaddr(addr.clone()));
+				cursor = resaddr(addr);
+				out.push_str(&"-".repeat(n as usize));
+			}
+			BfOp::Mov(from_a, to_a) => {
+				let from_a = resaddr(from_a);
+				let to_a = resaddr(to_a);
+
+				assert!(from_a != to_a, "mov from same addr (infinite loop)");
+
+				cmov(out, cursor, from_a);
+				out.push_str("[-");
+				cmov(out, from_a, to_a);
+				out.push('+');
+				cmov(out, to_a, from_a);
+				out.push(']');
+				cursor = from_a;
+			}
+			BfOp::Dup(from_a, to_a1, to_a2) => {
+				let from_a = resaddr(from_a);
+				let to_a1 = resaddr(to_a1);
+				let to_a2 = resaddr(to_a2);
+
+				assert!(from_a != to_a1, "dup from same addr (infinite loop)");
+				assert!(from_a != to_a2, "dup from same addr (infinite loop)");
+
+				cmov(out, cursor, from_a);
+				out.push_str("[-");
+				cmov(out, from_a, to_a1);
+				out.push('+');
+				cmov(out, to_a1, to_a2);
+				out.push('+');
+				cmov(out, to_a2, from_a);
+				out.push(']');
+				cursor = from_a;
+			}
+			BfOp::Loop(addr, ops) => {
+				let addr = resaddr(addr);
+				cmov(out, cursor, addr);
+				out.push('[');
+				cursor = printbfi(out, ops, addr);
+				cmov(out, cursor, addr);
+				out.push(']');
+				cursor = addr;
+			}
+			BfOp::Loop2(addr1, addr2, ops) => {
+				let addr1 = resaddr(addr1);
+				let addr2 = resaddr(addr2);
+				cmov(out, cursor, addr1);
+				out.push('[');
+				cursor = printbfi(out, ops, addr1);
+				cmov(out, cursor, addr2);
+				out.push(']');
+				cursor = addr2;
+			}
 
-		mainloop.append(&mut code);
+			BfOp::Tag(..) | BfOp::Comment(_) | BfOp::Nop => {}
+			BfOp::Frame(_) => unreachable!("frame was never sized"),
+		}
 	}
 
-	root.push(BfOp::Loop(fixed_addr(0), mainloop));
+	cursor
+}
 
-	let mut out = String::from("");
-	printast(&mut out, root);
-	out
+// chop plain brainfuck into lines of at most `width` chars
+fn wrapbf(code: &str, width: usize) -> String {
+	let chars = code.chars().collect::<Vec<char>>();
+
+	chars
+		.chunks(width.max(1))
+		.map(|l| l.iter().collect::<String>())
+		.collect::<Vec<String>>()
+		.join("\n")
 }
 
 fn printast(out: &mut String, ast: Vec<BfOp>) {
@@ -2984,6 +5667,11 @@ fn printinstri(out: &mut String, ins: BfOp, cstart: usize, i: usize) -> usize {
 			cursor = resaddr(addr.clone());
 		}
 
+		BfOp::Getch(addr) => {
+			write!(out, "{},", cmov(cursor, resaddr(addr.clone()))).unwrap();
+			cursor = resaddr(addr.clone());
+		}
+
 		BfOp::AddI(addr, n) => {
 			write!(
 				out,
@@ -3094,6 +5782,7 @@ fn printinstri(out: &mut String, ins: BfOp, cstart: usize, i: usize) -> usize {
 		}
 
 		BfOp::Nop => {}
+		BfOp::Frame(_) => unreachable!("frame was never sized"),
 	}
 
 	write!(out, "\n").unwrap();
@@ -3115,12 +5804,55 @@ fn uncop(ctx: &Ctx, op: &llvm_ir::Operand) -> u64 {
 		llvm_ir::Operand::ConstantOperand(c) => match c.deref() {
 			llvm_ir::constant::Constant::Int { value, .. } => *value,
 			llvm_ir::constant::Constant::Null { .. } => 0,
+			// pointers into the globals, see global_ptr
+			llvm_ir::constant::Constant::GlobalReference { name, .. } => {
+				global_ptr(ctx, name, 0)
+			}
 			llvm_ir::constant::Constant::GetElementPtr(gep) => {
-				eprintln!("{:?}",gep.address);
-				eprintln!("{:?}",gep.indices);
-				//ctx.globals.iter().find(|g| g.name == gep.address.)
-				0
-			},
+				let (name, mut ty) = match gep.address.deref() {
+					llvm_ir::constant::Constant::GlobalReference {
+						name,
+						ty,
+					} => (name, ty),
+					a => unimplemented!("gep off of {:?}", a),
+				};
+
+				// indices are signed whatever their width
+				let index = |i: &llvm_ir::ConstantRef| match i.deref() {
+					llvm_ir::constant::Constant::Int { bits, value } => {
+						((*value << (64 - bits)) as i64) >> (64 - bits)
+					}
+					i => unimplemented!("gep index {:?}", i),
+				};
+
+				// the first index steps over whole globals, the rest walk
+				// into whatever the last one landed on
+				let mut at = 0;
+				for (n, i) in gep.indices.iter().enumerate() {
+					let i = index(i);
+					if n == 0 {
+						at += i * type_cells(ty) as i64;
+						continue;
+					}
+
+					match ty.deref() {
+						llvm_ir::Type::ArrayType { element_type, .. }
+						| llvm_ir::Type::VectorType { element_type, .. } => {
+							at += i * type_cells(element_type) as i64;
+							ty = element_type;
+						}
+						llvm_ir::Type::StructType { element_types, .. } => {
+							at += element_types[..i as usize]
+								.iter()
+								.map(|t| type_cells(t) as i64)
+								.sum::<i64>();
+							ty = &element_types[i as usize];
+						}
+						t => unimplemented!("gep into {}", t),
+					}
+				}
+				global_ptr(ctx, name, at)
+			}
 			_ => unimplemented!("how tf we gonna store that {:?}", c.deref()),
 		},
 		_ => {
@@ -3129,6 +5861,39 @@ fn uncop(ctx: &Ctx, op: &llvm_ir::Operand) -> u64 {
 	}
 }
 
+// Stack pointers count from the first cell so a pointer is just the cell's
+// address, which for a global is where build put it
+fn global_ptr(ctx: &Ctx, name: &llvm_ir::Name, at: i64) -> u64 {
+	let g = ctx
+		.globals
+		.iter()
+		.find(|g| &g.name == name)
+		.unwrap_or_else(|| unimplemented!("pointer to {}", name));
+	(g.addr as i64 + at) as u64
+}
+
+// Everything scalar gets a cell to itself no matter how wide, so this is
+// just counting. Named structs need the module to look them up and nothing
+// uses them yet.
+fn type_cells(ty: &llvm_ir::Type) -> u64 {
+	match ty {
+		llvm_ir::Type::IntegerType { .. } | llvm_ir::Type::PointerType { .. } => 1,
+		llvm_ir::Type::ArrayType {
+			element_type,
+			num_elements,
+		}
+		| llvm_ir::Type::VectorType {
+			element_type,
+			num_elements,
+			..
+		} => *num_elements as u64 * type_cells(element_type),
+		llvm_ir::Type::StructType { element_types, .. } => {
+			element_types.iter().map(|t| type_cells(t)).sum()
+		}
+		t => unimplemented!("how many cells is a {}", t),
+	}
+}
+
 fn n2nam(n: &llvm_ir::Name) -> String {
 	match n {
 		llvm_ir::Name::Name(n) => *n.clone(),
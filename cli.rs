@@ -1,17 +1,264 @@
 use std::env;
-use std::process;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
 use std::path::Path;
+use std::process;
 
 mod bfcc;
+mod driver;
+mod interp;
+mod transpile;
+
+const USAGE: &str = "usage: bfcc [options] <file.c | file.ll | file.bc | ->...
+
+multiple inputs are linked together into one program.
+
+options:
+  -o <file>          write output to <file> instead of stdout
+  --emit=<kind>      bf, bf-annotated (default), bfop, layout, stats
+                     or json. c, rust, c-unchecked and rust-unchecked
+                     translate the bf into source for a native binary
+                     with the target's tape, see bfexec --help
+  --wrap=<n>         wrap --emit=bf output every n columns
+  -O<n>              optimization level, 0 (default) through 3. Also
+                     handed to clang when compiling c
+  --target <name>    runtime profile: classic (default), checked or host.
+                     classic and checked compile c for avr (16 bit int)
+                     when clang has it, host for clang's default triple
+                     like most verify cases
+  --tape <n>         cells of tape to budget for instead of the target's.
+                     Warns if the deepest non-recursive call chain won't
+                     fit
+  --stack-guard      check for room before every call and halt with 255
+                     just past the caller's frame if there isn't any
+  --entry <fn>       function to start executing (default main)
+  --nostdlib         don't link in the bundled libc
+  --check            don't emit anything, list every op that could seek
+                     left of the first cell or take a cell past 0 or 255.
+                     Ones out past a pointer can't be told and get listed
+                     as such
+  -h, --help         print this";
+
+fn die(msg: &str) -> ! {
+	eprintln!("bfcc: {}", msg);
+	process::exit(1);
+}
 
 fn main() {
-	if env::args().len() != 2 {
-		eprintln!("expected a single argument: path to llvm bytecode");
-		process::exit(1);
+	let mut opts = bfcc::Options::default();
+	let mut inputs: Vec<String> = vec![];
+	let mut output: Option<String> = None;
+	let mut wrap: Option<usize> = None;
+	let mut tape: Option<usize> = None;
+	let mut check = false;
+	let mut stdlib = true;
+	let mut source: Option<transpile::Flavor> = None;
+
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		// flags that take a value accept both `--flag value` and `--flag=value`
+		let (flag, inline) = match arg.find('=') {
+			Some(i) if arg.starts_with("--") => {
+				(arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+			}
+			_ => (arg.clone(), None),
+		};
+
+		let mut value = |name: &str| -> String {
+			inline
+				.clone()
+				.or_else(|| args.next())
+				.unwrap_or_else(|| die(&format!("{} expects a value", name)))
+		};
+
+		if flag == "-h" || flag == "--help" {
+			println!("{}", USAGE);
+			return;
+		} else if flag == "-o" {
+			output = Some(value("-o"));
+		} else if flag == "--emit" {
+			let kind = value("--emit");
+			source = transpile::Flavor::from_name(&kind);
+			opts.emit = match source {
+				Some(_) => bfcc::Emit::Bf { wrap: None },
+				None => bfcc::Emit::from_name(&kind)
+					.unwrap_or_else(|| die(&format!("unknown emit kind {}", kind))),
+			};
+		} else if flag == "--wrap" {
+			let w = value("--wrap");
+			wrap = Some(w.parse().unwrap_or_else(|_| {
+				die(&format!("--wrap expects a column count, got {}", w))
+			}));
+		} else if flag.starts_with("-O") {
+			opts.opt = match &flag[2..] {
+				"" => 1,
+				n => n
+					.parse()
+					.ok()
+					.filter(|n| *n <= 3)
+					.unwrap_or_else(|| die(&format!("bad opt level {}", flag))),
+			};
+		} else if flag == "--target" {
+			let name = value("--target");
+			opts.target = bfcc::target(&name)
+				.unwrap_or_else(|| die(&format!("unknown target {}", name)));
+		} else if flag == "--tape" {
+			let n = value("--tape");
+			tape = Some(n.parse().unwrap_or_else(|_| {
+				die(&format!("--tape expects a cell count, got {}", n))
+			}));
+		} else if flag == "--stack-guard" {
+			opts.stack_guard = true;
+		} else if flag == "--nostdlib" {
+			stdlib = false;
+		} else if flag == "--entry" {
+			opts.entry = value("--entry");
+		} else if flag == "--check" {
+			check = true;
+		} else if flag != "-" && flag.starts_with("-") {
+			die(&format!("unknown option {}\n{}", arg, USAGE));
+		} else if arg == "-" && inputs.iter().any(|i| i == "-") {
+			die("stdin can only be read once");
+		} else {
+			inputs.push(arg);
+		}
+	}
+
+	if wrap.is_some() {
+		match opts.emit {
+			bfcc::Emit::Bf { .. } if source.is_none() && !check => {
+				opts.emit = bfcc::Emit::Bf { wrap: wrap }
+			}
+			_ => die("--wrap only applies to --emit=bf"),
+		}
+	}
+
+	// whichever order it came in relative to --target
+	if let Some(t) = tape {
+		opts.target.tape_len = t;
+	}
+
+	if inputs.is_empty() {
+		die(USAGE);
 	}
 
-	let bcpath = env::args().nth(1).unwrap();
+	// anything clang builds, or stdin since llvm wants a path to read bitcode
+	// from, takes a detour through a temp dir
+	let workdir = env::temp_dir().join(format!("bfcc-{}", process::id()));
+	fs::create_dir_all(&workdir)
+		.unwrap_or_else(|e| die(&format!("{}: {}", workdir.display(), e)));
 
-	let bfcode = bfcc::compile(Path::new(&bcpath));
-	println!("{}", bfcode);
+	// rather than have clang fail on every c file when it doesn't know the
+	// target's triple, build for whatever it does know
+	let needs_clang = stdlib || inputs.iter().any(|i| i.ends_with(".c"));
+	if let Some(triple) = opts.target.triple {
+		if needs_clang && !driver::knows_triple(triple, &workdir) {
+			eprintln!(
+				"bfcc: warning: clang can't build for {}, using its default \
+				 triple instead",
+				triple
+			);
+			opts.target.triple = None;
+		}
+	}
+
+	// every input gets its own subdir so foo/a.c and bar/a.c don't stomp on
+	// each other's bitcode
+	let result = inputs
+		.iter()
+		.enumerate()
+		.map(|(i, input)| {
+			let dir = workdir.join(i.to_string());
+			fs::create_dir_all(&dir)
+				.map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+			if input == "-" {
+				let mut bc = vec![];
+				io::stdin()
+					.read_to_end(&mut bc)
+					.map_err(|e| format!("reading stdin: {}", e))?;
+
+				let bcpath = dir.join("stdin.bc");
+				fs::write(&bcpath, bc)
+					.map_err(|e| format!("{}: {}", bcpath.display(), e))
+					.map(|_| bcpath)
+			} else {
+				driver::to_bitcode(
+					Path::new(input),
+					opts.opt,
+					opts.target.triple,
+					&dir,
+				)
+			}
+		})
+		.collect::<Result<Vec<_>, _>>();
+
+	// Only whatever the inputs actually use out of it ends up in the program.
+	// Bringing your own bitcode shouldn't need clang around so we can go on
+	// without it, anything that needed it comes up undefined. It only gets
+	// built the first time for each triple.
+	if stdlib {
+		match driver::libc(opts.target.triple) {
+			Ok(libs) => opts.libs = libs,
+			Err(e) => eprintln!("bfcc: warning: no libc: {}", e),
+		}
+	}
+
+	if check {
+		let findings = result
+			.and_then(|bcpaths| bfcc::check_with(&bcpaths, &opts));
+		fs::remove_dir_all(&workdir).ok();
+
+		let findings = findings.unwrap_or_else(|e| die(&e));
+		for f in findings.iter() {
+			println!("{}", f);
+		}
+		// unknowns fail too, nothing's been shown about them
+		let unknown = findings.iter().filter(|f| f.unknown).count();
+		if findings.len() > unknown {
+			eprintln!(
+				"bfcc: {} ops could break the promise",
+				findings.len() - unknown
+			);
+		}
+		if unknown > 0 {
+			eprintln!(
+				"bfcc: {} ops go through pointers and couldn't be checked",
+				unknown
+			);
+		}
+		if !findings.is_empty() {
+			process::exit(1);
+		}
+		return;
+	}
+
+	let result = result.and_then(|bcpaths| bfcc::compile_with(&bcpaths, &opts));
+
+	fs::remove_dir_all(&workdir).ok();
+
+	let compiled = result.unwrap_or_else(|e| die(&e));
+	for w in compiled.warnings.iter() {
+		eprintln!("bfcc: warning: {}", w);
+	}
+	let mut bfcode = compiled.code;
+
+	if let Some(flavor) = source {
+		let prog = interp::Program::new(&bfcode)
+			.unwrap_or_else(|f| die(&f.to_string()));
+		let config = interp::Config {
+			tape: opts.target.tape_len,
+			..interp::Config::default()
+		};
+		bfcode = transpile::transpile(&prog, &config, flavor).trim_end().to_string();
+	}
+
+	match output {
+		Some(o) => File::create(&o)
+			.and_then(|mut f| writeln!(f, "{}", bfcode))
+			.unwrap_or_else(|e| die(&format!("{}: {}", o, e))),
+		None => println!("{}", bfcode),
+	}
 }
@@ -1,6 +1,9 @@
 use std::env;
 use std::fs;
 
+#[path = "../interp.rs"]
+mod interp;
+
 extern crate ncurses;
 
 enum Color {
@@ -18,18 +21,13 @@ enum Color {
 }
 
 struct State<'a> {
-	tape: Vec<u8>,
+	machine: interp::Machine,
 	annots: Vec<Option<String>>,
 
-	mp: usize,
+	// byte offset of the next instruction in code
 	pc: usize,
 
 	code: &'a str,
-
-	ic: usize,
-	input: Vec<u8>,
-
-	output: Vec<u8>,
 }
 
 const INSTRUCTS: &[char] = &['[', ']', '+', '-', '>', '<', ',', '.'];
@@ -39,165 +37,67 @@ fn is_instr(i: char) -> bool {
 }
 
 impl<'a> State<'a> {
-	fn new(code: &'a str, input: Vec<u8>) -> State<'a> {
-		State {
-			tape: vec![0],
+	fn new(code: &'a str, input: Vec<u8>) -> Result<State<'a>, interp::Fault> {
+		let prog = interp::Program::new(code)?;
+		let machine = interp::Machine::new(
+			prog,
+			interp::Config {
+				tape: 1 << 20,
+				..interp::Config::default()
+			},
+		)
+		.with_input(&input);
+
+		Ok(State {
+			pc: machine.prog.at[0],
+			machine: machine,
 			annots: vec![None],
 
-			mp: 0,
-			pc: code.find(INSTRUCTS).unwrap(),
-
 			code: code,
-
-			ic: 0,
-			input: input,
-
-			output: vec![],
-		}
+		})
 	}
 
-	fn nextop(&self) -> Result<(usize, Option<String>), ()> {
-		let from = self.pc + 1;
-		let nextpc = self.code[from..].find(INSTRUCTS);
-		if nextpc.is_none() {
-			return Err(());
-		}
+	// the last #annotation between the instruction at from and the next one
+	fn annot(&self, from: usize, to: usize) -> Option<String> {
+		let annot = self.code[from..to].rfind(|c| c == '#')? + from + 1;
+		let end = self.code[annot..]
+			.find(|c| char::is_whitespace(c) || is_instr(c))
+			.unwrap_or(self.code.len() - annot);
 
-		let nextpc = nextpc.unwrap() + from;
-
-		let annot = self.code[from..nextpc].rfind(|c| c == '#');
-		if annot.is_some() {
-			let annot = annot.unwrap() + 1;
-			let to = self.code[(from + annot)..]
-				.find(|c| char::is_whitespace(c) || is_instr(c))
-				.unwrap();
-
-			Ok((
-				nextpc,
-				Some(
-					self.code[(from + annot)..(from + annot + to)].to_string(),
-				),
-			))
-		} else {
-			Ok((nextpc, None))
-		}
+		Some(self.code[annot..annot + end].to_string())
 	}
 
-	fn prevop(&self) -> Result<(usize, Option<String>), ()> {
-		let from = self.pc - 1;
-		let nextpc = self.code[..from].rfind(INSTRUCTS);
-		if nextpc.is_none() {
-			return Err(());
+	// Run one instruction, giving back the annotation right before the next
+	// one if there is one.
+	fn next(&mut self) -> Result<Option<String>, String> {
+		if self.machine.halted() {
+			return Err("EOF".to_string());
 		}
 
-		let nextpc = nextpc.unwrap();
-
-		Ok((nextpc, None))
-	}
-
-	fn ch(&self, i: usize) -> char {
-		self.code.chars().nth(i).unwrap()
-	}
-
-	fn next(&self) -> Result<(State<'a>, Option<String>), &str> {
-		let mut n: State<'a> = State {
-			tape: self.tape.clone(),
-			annots: self.annots.clone(),
-
-			pc: self.pc,
-			mp: self.mp,
-
-			code: self.code,
-
-			ic: self.ic,
-			input: self.input.clone(),
-			output: self.output.clone(),
-		};
-
-		match self.ch(self.pc) {
-			'+' => {
-				if n.tape[n.mp] == 255 {
-					return Err("overflow");
-				}
+		self.machine.step().map_err(|f| f.err.to_string())?;
 
-				n.tape[n.mp] += 1
-			}
-			'-' => {
-				if n.tape[n.mp] == 0 {
-					return Err("underflow");
-				}
-
-				n.tape[n.mp] -= 1
-			}
-			'>' => {
-				if n.mp == n.tape.len() - 1 {
-					n.tape.push(0);
-					n.annots.push(None);
-				}
-				n.mp += 1;
-			}
-			'<' => {
-				if n.mp == 0 {
-					return Err("negative tape");
-				}
-
-				n.mp -= 1;
-			}
-			',' => {
-				n.tape[n.mp] = n.input[n.ic];
-				if n.ic < n.input.len() - 1 {
-					n.ic += 1
-				};
-			}
-			'.' => n.output.push(n.tape[n.mp]),
-			'[' => {
-				if n.tape[n.mp] == 0 {
-					let mut depth = 0;
-					n.pc += 1;
-
-					while depth > 0 || n.ch(n.pc) != ']' {
-						if n.ch(n.pc) == '[' {
-							depth += 1;
-						} else if n.ch(n.pc) == ']' {
-							depth -= 1;
-						}
-
-						n.pc += 1;
-					}
-				}
-			}
-			']' => {
-				if n.tape[n.mp] != 0 {
-					let mut depth = 0;
-					n.pc -= 1;
-
-					while depth > 0 || n.ch(n.pc) != '[' {
-						if n.ch(n.pc) == ']' {
-							depth += 1;
-						} else if n.ch(n.pc) == '[' {
-							depth -= 1;
-						}
+		let from = self.pc + 1;
+		if self.machine.halted() {
+			self.pc = self.code.len();
+			return Err("EOF".to_string());
+		}
+		self.pc = self.machine.prog.at[self.machine.pc];
 
-						n.pc -= 1;
-					}
-				}
-			}
-			_ => panic!("shouldn't try to handle {:#?}", n.ch(n.pc)),
+		// the annotation belongs to whichever cell we're on once we get there
+		let annot = if from < self.pc {
+			self.annot(from, self.pc)
+		} else {
+			None
 		};
 
-		let next = n.nextop();
-		if next.is_err() {
-			return Err("EOF");
+		if self.annots.len() < self.machine.mem.len() {
+			self.annots.resize(self.machine.mem.len(), None);
 		}
-
-		let (pc, annot) = next.unwrap();
-
-		n.pc = pc;
 		if annot.is_some() {
-			n.annots[n.mp] = annot.clone();
+			self.annots[self.machine.mp] = annot.clone();
 		}
 
-		Ok((n, annot))
+		Ok(annot)
 	}
 }
 
@@ -215,9 +115,9 @@ struct Debugger<'a> {
 const mem_wid: usize = 60;
 
 impl<'a> Debugger<'a> {
-	fn new(code: &'a str, input: Vec<u8>) -> Debugger<'a> {
+	fn new(state: State<'a>) -> Debugger<'a> {
 		Debugger {
-			state: State::new(code, input),
+			state: state,
 			root: ncurses::initscr(),
 			code_scroll: 0,
 			mem_scroll: 0,
@@ -237,7 +137,7 @@ impl<'a> Debugger<'a> {
 				self.code_scroll + l
 			}
 		} else {
-			let cells = self.cur().tape.len();
+			let cells = self.cur().machine.mem.len();
 
 			self.mem_scroll = if l + self.mem_scroll < 0 {
 				0
@@ -283,7 +183,9 @@ impl<'a> Debugger<'a> {
 
 		if index > self.cur().pc {
 			while index > self.cur().pc {
-				self.step();
+				if self.step().is_err() {
+					break;
+				}
 			}
 
 			if index != self.cur().pc {
@@ -304,10 +206,9 @@ impl<'a> Debugger<'a> {
 		let next = self.state.next();
 
 		if next.is_err() {
-			self.status = next.err().unwrap().to_string();
+			self.status = next.err().unwrap();
 			Err("step failed")
 		} else {
-			self.state = next.unwrap().0;
 			Ok(())
 		}
 	}
@@ -318,7 +219,9 @@ impl<'a> Debugger<'a> {
 		let before = self.cur().pc;
 
 		while self.cur().pc <= before {
-			self.step();
+			if self.step().is_err() {
+				break;
+			}
 		}
 	}
 
@@ -355,7 +258,7 @@ impl<'a> Debugger<'a> {
 				break;
 			}
 
-			let (next, found) = ret.unwrap();
+			let found = ret.unwrap();
 
 			if match (&annot, &found) {
 				(Some(a), Some(f)) => a == f,
@@ -364,8 +267,6 @@ impl<'a> Debugger<'a> {
 			} {
 				break;
 			}
-
-			self.state = next;
 		}
 	}
 
@@ -438,14 +339,14 @@ impl<'a> Debugger<'a> {
 
 		ncurses::wmove(self.root, top + 1, 0);
 		ncurses::waddstr(self.root, " input: ");
-		for (i, ch) in self.cur().input.iter().enumerate() {
-			if i == self.cur().ic {
+		for (i, ch) in self.cur().machine.input.iter().enumerate() {
+			if i == self.cur().machine.ic {
 				ncurses::wattron(self.root, ncurses::A_REVERSE());
 			}
 
 			ncurses::waddstr(self.root, &format!("{:?}", *ch as char));
 
-			if i == self.cur().ic {
+			if i == self.cur().machine.ic {
 				ncurses::wattroff(self.root, ncurses::A_REVERSE());
 			}
 		}
@@ -453,7 +354,7 @@ impl<'a> Debugger<'a> {
 		ncurses::wmove(self.root, top + 2, 0);
 		ncurses::waddstr(self.root, "output: ");
 
-		for ch in self.cur().output.iter() {
+		for ch in self.cur().machine.output.iter() {
 			ncurses::waddstr(self.root, &format!("{:?}", *ch as char));
 		}
 	}
@@ -472,11 +373,11 @@ impl<'a> Debugger<'a> {
 		if virt {
 			for i in 0..height - 1 {
 				let i = i + self.mem_scroll as i32;
-				if i >= self.cur().tape.len() as i32 {
+				if i >= self.cur().machine.mem.len() as i32 {
 					continue;
 				}
 
-				if self.cur().mp == i as usize {
+				if self.cur().machine.mp == i as usize {
 					ncurses::wmove(
 						self.root,
 						i + top + 1 - (self.mem_scroll as i32),
@@ -498,14 +399,14 @@ impl<'a> Debugger<'a> {
 					self.root,
 					&format!(
 						"{:03} / {:03} : ",
-						(i as i32 - self.cur().mp as i32).abs(),
+						(i as i32 - self.cur().machine.mp as i32).abs(),
 						i
 					),
 				);
 
-				if self.cur().tape[i as usize] == 0 {
+				if self.cur().machine.mem[i as usize] == 0 {
 					ncurses::wcolor_set(self.root, Color::Mem0 as i16);
-				} else if self.cur().tape[i as usize] == 1 {
+				} else if self.cur().machine.mem[i as usize] == 1 {
 					ncurses::wcolor_set(self.root, Color::Mem1 as i16);
 				} else {
 					ncurses::wcolor_set(self.root, Color::Mem as i16);
@@ -515,9 +416,9 @@ impl<'a> Debugger<'a> {
 					self.root,
 					&format!(
 						"{v:03} / {c:01}",
-						v = self.cur().tape[i as usize],
-						c = if self.cur().tape[i as usize] >= 32 {
-							self.cur().tape[i as usize] as char
+						v = self.cur().machine.mem[i as usize],
+						c = if self.cur().machine.mem[i as usize] >= 32 {
+							self.cur().machine.mem[i as usize] as u8 as char
 						} else {
 							' '
 						}
@@ -526,7 +427,7 @@ impl<'a> Debugger<'a> {
 
 				ncurses::wattroff(self.root, ncurses::A_REVERSE());
 
-				if self.cur().annots[i as usize].is_some() {
+				if self.cur().annots.get(i as usize).map_or(false, |a| a.is_some()) {
 					ncurses::wcolor_set(self.root, Color::Annot as i16);
 					ncurses::waddstr(
 						self.root,
@@ -542,7 +443,8 @@ impl<'a> Debugger<'a> {
 			ncurses::wmove(self.root, top + 1, left);
 			let mut tapestr = self
 				.cur()
-				.tape
+				.machine
+				.mem
 				.iter()
 				.map(|cell| format!("{:03}", cell))
 				.collect::<Vec<String>>()
@@ -552,8 +454,8 @@ impl<'a> Debugger<'a> {
 
 			ncurses::waddstr(self.root, &tapestr);
 
-			if self.cur().mp * 4 < ncurses::getmaxx(self.root) as usize {
-				ncurses::wmove(self.root, top + 2, (self.cur().mp * 4) as i32);
+			if self.cur().machine.mp * 4 < ncurses::getmaxx(self.root) as usize {
+				ncurses::wmove(self.root, top + 2, (self.cur().machine.mp * 4) as i32);
 				ncurses::waddch(self.root, ncurses::ACS_UARROW());
 			}
 		}
@@ -660,7 +562,15 @@ fn main() {
 	// technically incorrect but at least it won't break rendering
 	let code = code.replace('\t', "    ");
 
-	let mut d = Debugger::new(&code, input.as_bytes().to_vec());
+	let state = match State::new(&code, input.as_bytes().to_vec()) {
+		Ok(state) => state,
+		Err(f) => {
+			println!("{}", f);
+			return;
+		}
+	};
+
+	let mut d = Debugger::new(state);
 
 	d.init();
 	d.draw(bp);
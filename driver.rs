@@ -0,0 +1,271 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+
+// Getting from whatever source the user hands us to bitcode bfcc can eat. The
+// actual heavy lifting is all clang, we just know which flags to pass.
+
+// stdfuck.h and the libc it pulls in, baked in so bfcc works wherever it ends
+// up. They all get unpacked into one dir, which is also what clang gets as its
+// include path.
+const LIBC: &[(&str, &str)] = &[
+	("stdfuck.h", include_str!("stdfuck.h")),
+	("ctype.h", include_str!("libc/ctype.h")),
+	("ctype.c", include_str!("libc/ctype.c")),
+	("stdio.h", include_str!("libc/stdio.h")),
+	("stdio.c", include_str!("libc/stdio.c")),
+	("stdlib.h", include_str!("libc/stdlib.h")),
+	("stdlib.c", include_str!("libc/stdlib.c")),
+	("string.h", include_str!("libc/string.h")),
+	("string.c", include_str!("libc/string.c")),
+];
+
+// Everything unpacked or built from LIBC lives under here. The name changes
+// along with the sources so a different bfcc never picks up stale files.
+fn cache_dir() -> PathBuf {
+	let mut h = DefaultHasher::new();
+	LIBC.hash(&mut h);
+	env::temp_dir().join(format!("bfcc-libc-{:016x}", h.finish()))
+}
+
+// Fill in `dir` with `make` unless an earlier run already did. It gets made
+// somewhere of our own and moved over in one go so another bfcc running at
+// the same time never sees half of it.
+fn once<F>(dir: &Path, make: F) -> Result<(), String>
+where
+	F: FnOnce(&Path) -> Result<(), String>,
+{
+	if dir.is_dir() {
+		return Ok(());
+	}
+
+	let tmp = dir.with_extension(format!("tmp{}", process::id()));
+	fs::create_dir_all(&tmp)
+		.map_err(|e| format!("{}: {}", tmp.display(), e))?;
+	let made = make(&tmp);
+	if made.is_ok() && fs::rename(&tmp, dir).is_ok() {
+		return Ok(());
+	}
+
+	// lost the race to someone else, theirs is just as good
+	fs::remove_dir_all(&tmp).ok();
+	made
+}
+
+fn include_dir() -> Result<PathBuf, String> {
+	let dir = cache_dir().join("src");
+	once(&dir, |tmp| {
+		LIBC.iter().try_for_each(|(name, src)| {
+			let path = tmp.join(name);
+			fs::write(&path, src)
+				.map_err(|e| format!("{}: {}", path.display(), e))
+		})
+	})?;
+	Ok(dir)
+}
+
+// CLANG in the environment wins so folks can point at a specific version
+fn tool(name: &str, env_var: &str) -> String {
+	env::var(env_var).unwrap_or(name.to_string())
+}
+
+fn run(tool: &str, args: &[&OsStr]) -> Result<(), String> {
+	let out = Command::new(tool).args(args).output().map_err(|e| {
+		match e.kind() {
+			io::ErrorKind::NotFound => format!(
+				"couldn't find `{}`, is llvm/clang installed and on your PATH?",
+				tool
+			),
+			_ => format!("running {}: {}", tool, e),
+		}
+	})?;
+
+	let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+
+	match (out.status.success(), stderr.is_empty()) {
+		(true, _) => Ok(()),
+		(false, true) => Err(format!("{} failed: {}", tool, out.status)),
+		(false, false) => Err(stderr),
+	}
+}
+
+// c -> bitcode with whatever flags the caller wants on top of the usual.
+// There's no libc underneath us so don't let clang assume one, ours gets
+// linked in after. Otherwise -O1 and up turn things like puts("") into calls
+// to whatever clang thinks putchar looks like.
+pub fn clang_bc(flags: &[&str], from: &Path, to: &Path) -> Result<(), String> {
+	let include = include_dir()?;
+	let mut args: Vec<&OsStr> = flags
+		.iter()
+		.filter(|f| !f.is_empty())
+		.map(OsStr::new)
+		.collect();
+
+	args.append(&mut vec![
+		OsStr::new("-ffreestanding"),
+		OsStr::new("-emit-llvm"),
+		OsStr::new("-I"),
+		include.as_os_str(),
+		OsStr::new("-c"),
+		from.as_os_str(),
+		OsStr::new("-o"),
+		to.as_os_str(),
+	]);
+
+	run(&tool("clang", "CLANG"), &args)
+}
+
+// c all the way to a program for the machine we're on, for comparing
+// against what bfcc makes of the same code. Only verify's fuzzer does that.
+#[allow(dead_code)]
+pub fn clang_native(
+	flags: &[&str],
+	sources: &[PathBuf],
+	to: &Path,
+) -> Result<(), String> {
+	let include = include_dir()?;
+	let mut args: Vec<&OsStr> = flags.iter().map(OsStr::new).collect();
+	args.append(&mut vec![
+		OsStr::new("-I"),
+		include.as_os_str(),
+		OsStr::new("-o"),
+		to.as_os_str(),
+	]);
+	args.extend(sources.iter().map(|s| s.as_os_str()));
+
+	run(&tool("clang", "CLANG"), &args)
+}
+
+// human readable ir next to the bitcode, nice for poking around in verify's
+// artifacts
+#[allow(dead_code)]
+pub fn llvm_dis(bc: &Path) -> Result<(), String> {
+	run(&tool("llvm-dis", "LLVM_DIS"), &[bc.as_os_str()])
+}
+
+#[allow(dead_code)]
+fn llvm_as(from: &Path, to: &Path) -> Result<(), String> {
+	run(
+		&tool("llvm-as", "LLVM_AS"),
+		&[from.as_os_str(), OsStr::new("-o"), to.as_os_str()],
+	)
+}
+
+fn c_flags<'a>(opt: &'a str, target: &'a Option<String>) -> Vec<&'a str> {
+	let mut flags = vec![opt];
+	if let Some(t) = target {
+		flags.push(t.as_str());
+	}
+	flags
+}
+
+// clang's own complaint plus what to do about it when it was building for
+// our triple, which trips over things the host triple doesn't
+fn for_triple(err: String, triple: Option<&str>) -> String {
+	match triple {
+		Some(t) => format!(
+			"{}\nbfcc: compiling c for {}, --target host uses clang's default \
+			 triple instead",
+			err, t
+		),
+		None => err,
+	}
+}
+
+// Whether clang builds for `triple` at all, avr is an experimental target
+// plenty of clangs get built without. A clang that can't build anything
+// gets the benefit of the doubt so the real error comes from whatever needed
+// it. Only the cli has a triple to fall back from.
+#[allow(dead_code)]
+pub fn knows_triple(triple: &str, workdir: &Path) -> bool {
+	let src = workdir.join("probe.c");
+	let out = workdir.join("probe.bc");
+	if fs::write(&src, "").is_err() {
+		return true;
+	}
+
+	let target = format!("--target={}", triple);
+	clang_bc(&[&target], &src, &out).is_ok()
+		|| clang_bc(&[], &src, &out).is_err()
+}
+
+// Turn a .c, .ll or .bc into a bitcode file. Anything that needs building
+// lands in `workdir`. verify only ever starts from .c so it's the cli's.
+#[allow(dead_code)]
+pub fn to_bitcode(
+	input: &Path,
+	opt: u8,
+	triple: Option<&str>,
+	workdir: &Path,
+) -> Result<PathBuf, String> {
+	let stem = input
+		.file_stem()
+		.ok_or(format!("{} isn't a file", input.display()))?;
+	let out = workdir.join(stem).with_extension("bc");
+
+	match input.extension().and_then(OsStr::to_str) {
+		Some("bc") => Ok(input.to_path_buf()),
+		Some("ll") => llvm_as(input, &out).map(|_| out),
+		Some("c") => {
+			let opt = format!("-O{}", opt);
+			let target = triple.map(|t| format!("--target={}", t));
+
+			clang_bc(&c_flags(&opt, &target), input, &out)
+				.map(|_| out)
+				.map_err(|e| for_triple(e, triple))
+		}
+		_ => Err(format!(
+			"{}: don't know what to do with this, expected a .c, .ll or .bc",
+			input.display()
+		)),
+	}
+}
+
+// Build every .c in the libc to bitcode in `workdir`. Always -O0, the library
+// is written for what bfcc handles straight out of clang and the optimizer is
+// free to turn it into things it doesn't.
+pub fn libc_into(
+	triple: Option<&str>,
+	workdir: &Path,
+) -> Result<Vec<PathBuf>, String> {
+	let include = include_dir()?;
+	let target = triple.map(|t| format!("--target={}", t));
+	LIBC.iter()
+		.filter(|(name, _)| name.ends_with(".c"))
+		.map(|(name, _)| {
+			let out = workdir.join(name).with_extension("bc");
+			clang_bc(&c_flags("-O0", &target), &include.join(name), &out)
+				.map(|_| out)
+		})
+		.collect::<Result<_, _>>()
+		.map_err(|e| for_triple(e, triple))
+}
+
+// The libc for `triple`, built the first time it's asked for and kept around
+// for next time. A different clang gets its own build. Only the cli wants one
+// that lasts, verify builds its own next to the test artifacts.
+#[allow(dead_code)]
+pub fn libc(triple: Option<&str>) -> Result<Vec<PathBuf>, String> {
+	let clang = tool("clang", "CLANG");
+	let mut h = DefaultHasher::new();
+	(&clang, triple).hash(&mut h);
+	let dir = cache_dir().join(format!(
+		"{}-{:016x}",
+		triple.unwrap_or("host"),
+		h.finish()
+	));
+
+	once(&dir, |tmp| libc_into(triple, tmp).map(|_| ()))?;
+	Ok(LIBC
+		.iter()
+		.filter(|(name, _)| name.ends_with(".c"))
+		.map(|(name, _)| dir.join(name).with_extension("bc"))
+		.collect())
+}
@@ -2,37 +2,158 @@ use std::env;
 use std::fs;
 use std::io;
 use std::io::Read;
-use std::io::Write;
-use std::time;
+use std::process;
+
+#[path = "../interp.rs"]
+mod interp;
+#[path = "../jit.rs"]
+mod jit;
+mod search;
+#[path = "../transpile.rs"]
+mod transpile;
+
+use interp::{Config, Fault, Machine, Program};
+
+const USAGE: &str = "usage: bfexec [options] <file.bf | ->
+
+runs a brainfuck program with stdin and stdout hooked up to `,` and `.`.
+`,` at the end of input reads a 0.
+
+options:
+  --tape <n>        cells of tape (default 30000)
+  --cell-bits <n>   8 (default), 16 or 32
+  --steps <n>       give up after running this many instructions
+  --wrap            let cells wrap around instead of it being an error
+  --plain           one instruction at a time, no fusing common loops into
+                    single ops. Slower, but otherwise the same
+  --jit             compile to x86-64 first, still checking everything
+  --jit-unchecked   compile to x86-64 without any checks, for programs known
+                    to behave. Cells wrap, there's no step limit and leaving
+                    the tape is a crash
+  --emit <kind>     print the program as c or rust source instead of running
+                    it: c, rust, c-unchecked or rust-unchecked. Checked code
+                    stops with the statuses below (blaming the start of the
+                    fused op), unchecked code wraps and trusts the pointer.
+                    Takes --tape, --cell-bits and --wrap into account but
+                    never counts steps
+  -h, --help        print this
+
+exits 0 when the program halts, otherwise:
+  10 cell overflow    11 cell underflow
+  12 seek past end    13 seek left of cell 0
+  14 unbalanced []    15 step limit hit
+   2 bad usage or i/o
+
+bfexec --search <relation> looks for the shortest program computing a
+relation instead, see bfexec --search --help.";
+
+fn die(msg: &str) -> ! {
+    eprintln!("bfexec: {}", msg);
+    process::exit(2);
+}
+
+fn fail(fault: Fault) -> ! {
+    eprintln!("bfexec: {}", fault);
+    process::exit(fault.err.status());
+}
 
-/*
 fn main() {
-    return
+    let mut config = Config::default();
+    let mut path: Option<String> = None;
+    // Some(checked) to jit
+    let mut jit = None;
+    let mut emit = None;
+
+    let argv: Vec<String> = env::args().skip(1).collect();
+    if argv.first().is_some_and(|a| a == "--search") {
+        search::main(&argv[1..]);
+        return;
+    }
 
-    let mut pathstr = "".to_owned();
+    let mut args = argv.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> usize {
+            let v = args
+                .next()
+                .unwrap_or_else(|| die(&format!("{} expects a value", name)));
+            v.parse()
+                .unwrap_or_else(|_| die(&format!("{} expects a number, got {}", name, v)))
+        };
 
-    for arg in env::args().skip(1).by_ref() {
-        if arg == "-" {
-            pathstr = "/dev/stdin".to_owned();
-        } else {
-            pathstr = arg;
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--tape" => config.tape = value("--tape").max(1),
+            "--cell-bits" => {
+                config.cell_bits = match value("--cell-bits") {
+                    b @ 8 | b @ 16 | b @ 32 => b as u32,
+                    b => die(&format!("cells can be 8, 16 or 32 bits, not {}", b)),
+                }
+            }
+            "--steps" => config.steps = Some(value("--steps") as u64),
+            "--wrap" => config.wrap = true,
+            "--plain" => config.optimize = false,
+            "--jit" => jit = Some(true),
+            "--jit-unchecked" => jit = Some(false),
+            "--emit" => {
+                let kind = args
+                    .next()
+                    .unwrap_or_else(|| die("--emit expects a value"));
+                emit = Some(
+                    transpile::Flavor::from_name(&kind)
+                        .unwrap_or_else(|| die(&format!("unknown emit kind {}", kind))),
+                );
+            }
+            a if a != "-" && a.starts_with("-") => {
+                die(&format!("unknown option {}\n{}", a, USAGE))
+            }
+            _ if path.is_some() => die("expected one program"),
+            _ => path = Some(arg),
         }
     }
 
-    if pathstr == "" {
-        panic!("expected at least one arg");
+    let path = path.unwrap_or_else(|| die(USAGE));
+    let code = if path == "-" {
+        let mut code = String::new();
+        io::stdin()
+            .read_to_string(&mut code)
+            .unwrap_or_else(|e| die(&format!("reading stdin: {}", e)));
+        code
+    } else {
+        fs::read_to_string(&path).unwrap_or_else(|e| die(&format!("{}: {}", path, e)))
+    };
+
+    if let Some(flavor) = emit {
+        let prog = Program::new(&code).unwrap_or_else(|f| fail(f));
+        print!("{}", transpile::transpile(&prog, &config, flavor));
+        return;
+    }
+
+    if jit.is_some() {
+        if let Some(why) = jit::unsupported(&config) {
+            die(&why);
+        }
     }
 
-    let code = fs::read_to_string(pathstr).unwrap();
+    let prog = Program::new(&code).unwrap_or_else(|f| fail(f));
+    let mut machine = Machine::new(prog, config)
+        .output_to(Box::new(io::BufWriter::new(io::stdout())));
 
-    let out = exec(&code, None).unwrap();
+    // the program already took stdin so it gets no input
+    if path != "-" {
+        machine = machine.input_from(Box::new(io::stdin()));
+    }
 
-    let stdout = io::stdout();
-    let mut out_handle = stdout.lock();
-    out_handle.write(out.as_slice()).unwrap();
-    out_handle.flush().unwrap();
+    let result = match jit {
+        Some(checked) => jit::run(&mut machine, checked),
+        None => machine.run(),
+    };
+    if let Err(f) = result {
+        fail(f);
+    }
 }
-*/
 
 /*
 #[test]
@@ -99,220 +220,3 @@ fn verify_eq() {
     }
 }
 */
-
-fn map2(code: &[u8]) -> String {
-    code.iter()
-        .map(|b| match b {
-            0 => '_',
-            1 => '+',
-            2 => '-',
-            3 => '>',
-            4 => '<',
-            5 => '[',
-            6 => ']',
-            7 => 'b',
-
-            9 => ',',
-            10 => '.',
-            _ => ' ',
-        })
-        .collect()
-}
-
-enum Op {
-    Add = 1,
-    Sub = 2,
-    Rit = 3,
-    Lef = 4,
-    Lop = 5,
-    Fin = 6,
-
-    Inp = 9,
-    Out = 10,
-}
-
-fn main() {
-    let mut code: [u8; 10] = [0; 10];
-
-    let start = std::time::Instant::now();
-
-    let len = 10;
-
-    let mut max: u64 = 0;
-
-    for i in 0..len {
-        max |= 0b111 << (3 * i);
-    }
-
-    let max = max;
-
-    'next: for c in 0..=max {
-        for i in 0..len {
-            code[i] = ((c >> (3 * i)) & 0b111) as u8;
-        }
-
-        if c % 10000000 == 0 {
-            println!(
-                "{:04}s {:0.0} Milop/s {:010} / {} {:.2}% {}",
-                start.elapsed().as_secs(),
-                ((c as f64) / 1_000_000.0) / (start.elapsed().as_secs() as f64),
-                c,
-                max,
-                (c as f64 / max as f64) * 100.0,
-                map2(&code[..]),
-            );
-        }
-
-        for l in 0..=255 {
-            for r in 0..=255 {
-                // let expected = vec![(l == r) as u8];
-                // let expected = (l < r) as u8;
-                let expected = (l != 0 && r != 0);
-                //let expected = l == r;
-
-                let mut mem: [u8; 20] = [0; 20];
-                mem[0] = l;
-                mem[1] = r;
-
-                let actual = exec(&mut mem, &code[..], 1000);
-
-                if actual.is_err() {
-                    continue 'next;
-                }
-
-                if expected != (actual.unwrap() != 0) {
-                    continue 'next;
-                }
-            }
-        }
-
-        println!("found #{} {}", c, map2(&code[..]));
-        return;
-    }
-}
-
-#[derive(Debug)]
-enum InterpErr {
-    IntOverflow,
-    IntUnderflow,
-    MemOverflow,
-    MemUnderflow,
-    LoopMemOverflow,
-    LoopMemUnderflow,
-    TooManySteps,
-    Unbalanced,
-}
-
-fn exec(mem: &mut [u8], code: &[u8], stepmax: usize) -> Result<u8, InterpErr> {
-    let mut pc = 0;
-    let mut mp = 0;
-    let mut step = 0;
-
-    if mem
-        .iter()
-        .map(|x| match x {
-            _ => 0,
-            6 => -1,
-            5 => 1,
-        })
-        .sum::<i64>()
-        != 0
-    {
-        return Err(InterpErr::Unbalanced);
-    }
-
-    while pc < code.len() {
-        match code[pc] {
-            1 => {
-                // +
-                if mem[mp] == 255 {
-                    return Err(InterpErr::IntOverflow);
-                }
-                mem[mp] += 1
-            }
-            2 => {
-                // -
-                if mem[mp] == 0 {
-                    return Err(InterpErr::IntUnderflow);
-                }
-                mem[mp] -= 1
-            }
-            3 => {
-                // >
-                if mp == mem.len() - 1 {
-                    return Err(InterpErr::MemOverflow);
-                }
-                mp += 1
-            }
-            4 => {
-                // <
-                if mp == 0 {
-                    return Err(InterpErr::MemUnderflow);
-                }
-
-                mp -= 1
-            }
-            5 => {
-                // [
-                if mem[mp] == 0 {
-                    let mut depth = 0;
-                    pc += 1;
-
-                    if pc == code.len() {
-                        return Err(InterpErr::LoopMemOverflow);
-                    }
-
-                    while depth > 0 || *code.get(pc).unwrap() != (Op::Fin as u8) {
-                        if *code.get(pc).unwrap() == (Op::Lop as u8) {
-                            depth += 1;
-                        } else if *code.get(pc).unwrap() == (Op::Fin as u8) {
-                            depth -= 1;
-                        }
-
-                        pc += 1;
-
-                        if code.get(pc).is_none() {
-                            return Err(InterpErr::LoopMemOverflow);
-                        }
-                    }
-                }
-            }
-            6 => {
-                // ]
-                if mem[mp] != 0 {
-                    let mut depth = 0;
-
-                    if pc == 0 {
-                        return Err(InterpErr::LoopMemUnderflow);
-                    }
-
-                    pc -= 1;
-
-                    while depth > 0 || *code.get(pc).unwrap() != (Op::Lop as u8) {
-                        if *code.get(pc).unwrap() == (Op::Fin as u8) {
-                            depth += 1;
-                        } else if *code.get(pc).unwrap() == (Op::Lop as u8) {
-                            depth -= 1;
-                        }
-
-                        if pc == 0 {
-                            return Err(InterpErr::LoopMemUnderflow);
-                        }
-
-                        pc -= 1;
-                    }
-                }
-            }
-            _ => {}
-        };
-
-        pc += 1;
-        step += 1;
-
-        if step > stepmax {
-            return Err(InterpErr::TooManySteps);
-        }
-    }
-
-    Ok(mem[mp])
-}
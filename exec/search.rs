@@ -0,0 +1,503 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use super::die;
+use interp::{Config, Machine, Program};
+
+const USAGE: &str = "usage: bfexec --search <relation> [options]
+
+finds the shortest strict brainfuck program (no wrapping cells, never
+leaving the tape) that computes <relation> for every input. inputs go in
+cells 0, 1, .. and the head starts on cell 0.
+
+relations:
+  and or eq ne lt le gt ge      two inputs, out is 0 or 1
+  not bool lsb                  one input, out is 0 or 1
+  add sub                       two inputs, only where the result fits
+  copy                          one input, out gets the same value
+
+options:
+  --cells <n>       tape the program may use (default inputs + 3)
+  --out <c>         cell the answer goes in (default the one after the inputs)
+  --zero <c,c,..>   cells that must be 0 afterwards (default every cell that
+                    isn't an input or the out cell, empty for none)
+  --keep            inputs must still hold their values afterwards
+  --truthy          only care whether out is 0 or not
+  --end <c|any>     cell the head has to finish on (default 0)
+  --range <lo-hi>   values each input takes (default 0-255)
+  --max-len <n>     longest program to try (default 12)
+  --steps <n>       steps a run may take before it counts as a hang (default
+                    5000)
+  --threads <n>     default every core";
+
+struct Relation {
+    name: &'static str,
+    inputs: usize,
+    // None when the inputs are outside what the relation is defined for,
+    // those cases aren't checked
+    want: fn(&[u8]) -> Option<u8>,
+}
+
+const RELATIONS: &[Relation] = &[
+    Relation {
+        name: "and",
+        inputs: 2,
+        want: |i| Some((i[0] != 0 && i[1] != 0) as u8),
+    },
+    Relation {
+        name: "or",
+        inputs: 2,
+        want: |i| Some((i[0] != 0 || i[1] != 0) as u8),
+    },
+    Relation {
+        name: "eq",
+        inputs: 2,
+        want: |i| Some((i[0] == i[1]) as u8),
+    },
+    Relation {
+        name: "ne",
+        inputs: 2,
+        want: |i| Some((i[0] != i[1]) as u8),
+    },
+    Relation {
+        name: "lt",
+        inputs: 2,
+        want: |i| Some((i[0] < i[1]) as u8),
+    },
+    Relation {
+        name: "le",
+        inputs: 2,
+        want: |i| Some((i[0] <= i[1]) as u8),
+    },
+    Relation {
+        name: "gt",
+        inputs: 2,
+        want: |i| Some((i[0] > i[1]) as u8),
+    },
+    Relation {
+        name: "ge",
+        inputs: 2,
+        want: |i| Some((i[0] >= i[1]) as u8),
+    },
+    Relation {
+        name: "not",
+        inputs: 1,
+        want: |i| Some((i[0] == 0) as u8),
+    },
+    Relation {
+        name: "bool",
+        inputs: 1,
+        want: |i| Some((i[0] != 0) as u8),
+    },
+    Relation {
+        name: "lsb",
+        inputs: 1,
+        want: |i| Some(i[0] & 1),
+    },
+    Relation {
+        name: "add",
+        inputs: 2,
+        want: |i| i[0].checked_add(i[1]),
+    },
+    Relation {
+        name: "sub",
+        inputs: 2,
+        want: |i| i[0].checked_sub(i[1]),
+    },
+    Relation {
+        name: "copy",
+        inputs: 1,
+        want: |i| Some(i[0]),
+    },
+];
+
+struct Spec {
+    relation: &'static Relation,
+    cells: usize,
+    out: usize,
+    zero: Vec<usize>,
+    keep: bool,
+    truthy: bool,
+    end: Option<usize>,
+    steps: usize,
+
+    // every input tuple the relation is defined on, the likely troublemakers
+    // (ends of the range) first so most candidates die quickly
+    cases: Vec<(Vec<u8>, u8)>,
+}
+
+impl Spec {
+    // a machine for holds to reuse, one per worker
+    fn machine(&self) -> Machine {
+        Machine::new(
+            Program::new("").unwrap(),
+            Config {
+                tape: self.cells,
+                steps: Some(self.steps as u64),
+                // fusing loops doesn't pay off on a handful of instructions
+                optimize: false,
+                ..Config::default()
+            },
+        )
+    }
+
+    fn holds(&self, machine: &mut Machine, code: &[u8]) -> bool {
+        // brackets get matched once here rather than for every case
+        if machine.load(code).is_err() {
+            return false;
+        }
+
+        for (input, want) in self.cases.iter() {
+            machine.reset();
+            for (c, &v) in input.iter().enumerate() {
+                machine.poke(c, v as u32);
+            }
+
+            if machine.run().is_err() {
+                return false;
+            }
+            let mp = machine.mp;
+
+            if self.end.is_some_and(|end| end != mp) {
+                return false;
+            }
+
+            let got = machine.cell(self.out) as u8;
+            if self.truthy && (got != 0) != (*want != 0) || !self.truthy && got != *want {
+                return false;
+            }
+
+            if self.zero.iter().any(|&c| machine.cell(c) != 0) {
+                return false;
+            }
+
+            if self.keep
+                && input
+                    .iter()
+                    .enumerate()
+                    .any(|(c, &v)| c != self.out && machine.cell(c) != v as u32)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn cases(relation: &Relation, lo: u8, hi: u8) -> Vec<(Vec<u8>, u8)> {
+    let mut edges: Vec<u8> = vec![lo, lo.saturating_add(1), hi.saturating_sub(1), hi]
+        .into_iter()
+        .filter(|v| *v >= lo && *v <= hi)
+        .collect();
+    edges.sort();
+    edges.dedup();
+
+    let mut all = vec![vec![]];
+    let mut probes = vec![vec![]];
+    for _ in 0..relation.inputs {
+        all = all
+            .into_iter()
+            .flat_map(|t: Vec<u8>| {
+                (lo..=hi).map(move |v| {
+                    let mut t = t.clone();
+                    t.push(v);
+                    t
+                })
+            })
+            .collect();
+        probes = probes
+            .into_iter()
+            .flat_map(|t: Vec<u8>| {
+                edges.iter().map(move |v| {
+                    let mut t = t.clone();
+                    t.push(*v);
+                    t
+                })
+            })
+            .collect();
+    }
+
+    let rest: Vec<Vec<u8>> = all.into_iter().filter(|t| !probes.contains(t)).collect();
+
+    probes
+        .into_iter()
+        .chain(rest)
+        .filter_map(|t| (relation.want)(&t).map(|want| (t, want)))
+        .collect()
+}
+
+const ALPHABET: &[u8] = b"+-><[]";
+
+// Whether c may follow prev in a canonical program. Pairs that cancel out,
+// loops that can never run or never stop, and walking left off cell 0
+// straight away all have a shorter (or no) equivalent, so they're skipped.
+fn canonical(prev: Option<u8>, c: u8) -> bool {
+    let pair = |p: &[u8; 2]| prev == Some(p[0]) && c == p[1];
+
+    !(pair(b"+-")
+        || pair(b"-+")
+        || pair(b"><")
+        || pair(b"<>")
+        || pair(b"[]")
+        || pair(b"][")
+        || prev.is_none() && c == b'<')
+}
+
+// How many loops are open after adding c to code, None if c can't go there
+// or the rest of the total instructions aren't enough to close them all.
+fn push_depth(code: &[u8], depth: usize, total: usize, c: u8) -> Option<usize> {
+    if !canonical(code.last().cloned(), c) {
+        return None;
+    }
+
+    let depth = if c == b'[' {
+        depth + 1
+    } else if c == b']' {
+        depth.checked_sub(1)?
+    } else {
+        depth
+    };
+
+    if depth > total - code.len() - 1 {
+        return None;
+    }
+
+    Some(depth)
+}
+
+// Every canonical program of exactly len instructions that starts with code,
+// in alphabet order, until found says stop. depth is how many [ are open.
+fn extend(
+    code: &mut Vec<u8>,
+    depth: usize,
+    len: usize,
+    found: &mut dyn FnMut(&[u8]) -> bool,
+) -> bool {
+    if code.len() == len {
+        return depth == 0 && found(code);
+    }
+
+    for &c in ALPHABET.iter() {
+        let depth = match push_depth(code, depth, len, c) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        code.push(c);
+        let stop = extend(code, depth, len, found);
+        code.pop();
+
+        if stop {
+            return true;
+        }
+    }
+
+    false
+}
+
+// Shortest program meeting the spec, the first one in alphabet order.
+fn shortest(spec: &Spec, max_len: usize, threads: usize) -> Option<Vec<u8>> {
+    for len in 1..=max_len {
+        let start = Instant::now();
+
+        // hand out fixed prefixes to the workers, lower prefixes are earlier
+        // in the order so the answer doesn't depend on who finishes first
+        let mut prefixes = vec![];
+        let mut open = vec![];
+        gen_prefixes(&mut vec![], 0, len.min(4), len, &mut prefixes, &mut open);
+
+        let next = AtomicUsize::new(0);
+        let best = AtomicUsize::new(usize::MAX);
+        let tried = AtomicUsize::new(0);
+        let hits = Mutex::new(vec![]);
+
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| {
+                    let mut machine = spec.machine();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= prefixes.len() || i > best.load(Ordering::Relaxed) {
+                            break;
+                        }
+
+                        let mut n = 0;
+                        let mut code = prefixes[i].clone();
+                        extend(&mut code, open[i], len, &mut |c| {
+                            n += 1;
+                            if spec.holds(&mut machine, c) {
+                                best.fetch_min(i, Ordering::Relaxed);
+                                hits.lock().unwrap().push((i, c.to_vec()));
+                                return true;
+                            }
+                            false
+                        });
+                        tried.fetch_add(n, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+
+        eprintln!(
+            "length {:2}: {} candidates in {:.2}s",
+            len,
+            tried.load(Ordering::Relaxed),
+            start.elapsed().as_secs_f64()
+        );
+
+        let mut hits = hits.into_inner().unwrap();
+        hits.sort();
+        if let Some((_, code)) = hits.into_iter().next() {
+            return Some(code);
+        }
+    }
+
+    None
+}
+
+// Canonical prefixes of len instructions that can still be closed off within
+// total instructions, along with how many loops each leaves open.
+fn gen_prefixes(
+    code: &mut Vec<u8>,
+    depth: usize,
+    len: usize,
+    total: usize,
+    prefixes: &mut Vec<Vec<u8>>,
+    open: &mut Vec<usize>,
+) {
+    if code.len() == len {
+        prefixes.push(code.clone());
+        open.push(depth);
+        return;
+    }
+
+    for &c in ALPHABET.iter() {
+        let depth = match push_depth(code, depth, total, c) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        code.push(c);
+        gen_prefixes(code, depth, len, total, prefixes, open);
+        code.pop();
+    }
+}
+
+fn cell_list(v: &str) -> Vec<usize> {
+    v.split(',')
+        .filter(|c| !c.is_empty())
+        .map(|c| {
+            c.parse()
+                .unwrap_or_else(|_| die(&format!("bad cell {} in --zero", c)))
+        })
+        .collect()
+}
+
+pub fn main(args: &[String]) {
+    let mut relation = None;
+    let mut cells = None;
+    let mut out = None;
+    let mut zero = None;
+    let mut keep = false;
+    let mut truthy = false;
+    let mut end = Some(0);
+    let mut range = (0, 255);
+    let mut max_len = 12;
+    let mut steps = 5000;
+    let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> String {
+            args.next()
+                .unwrap_or_else(|| die(&format!("{} expects a value", name)))
+                .clone()
+        };
+        let number = |name: &str, v: String| -> usize {
+            v.parse()
+                .unwrap_or_else(|_| die(&format!("{} expects a number, got {}", name, v)))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--cells" => cells = Some(number("--cells", value("--cells"))),
+            "--out" => out = Some(number("--out", value("--out"))),
+            "--zero" => zero = Some(cell_list(&value("--zero"))),
+            "--keep" => keep = true,
+            "--truthy" => truthy = true,
+            "--end" => {
+                end = match value("--end").as_str() {
+                    "any" => None,
+                    v => Some(number("--end", v.to_string())),
+                }
+            }
+            "--range" => {
+                let v = value("--range");
+                let mut ends = v.splitn(2, '-').map(|e| e.parse::<u8>());
+                range = match (ends.next(), ends.next()) {
+                    (Some(Ok(lo)), Some(Ok(hi))) if lo <= hi => (lo, hi),
+                    _ => die(&format!("--range wants lo-hi within 0-255, got {}", v)),
+                }
+            }
+            "--max-len" => max_len = number("--max-len", value("--max-len")),
+            "--steps" => steps = number("--steps", value("--steps")),
+            "--threads" => threads = number("--threads", value("--threads")).max(1),
+            a if a.starts_with("-") => die(&format!("unknown option {}\n{}", a, USAGE)),
+            _ if relation.is_some() => die("expected one relation"),
+            name => {
+                relation = Some(
+                    RELATIONS
+                        .iter()
+                        .find(|r| r.name == name)
+                        .unwrap_or_else(|| die(&format!("no relation called {}\n{}", name, USAGE))),
+                )
+            }
+        }
+    }
+
+    let relation = relation.unwrap_or_else(|| die(USAGE));
+    let cells = cells.unwrap_or(relation.inputs + 3);
+    let out = out.unwrap_or(relation.inputs);
+    let zero = zero.unwrap_or_else(|| (relation.inputs..cells).filter(|&c| c != out).collect());
+
+    if cells < relation.inputs
+        || out >= cells
+        || zero.iter().any(|&c| c >= cells)
+        || end.is_some_and(|e| e >= cells)
+    {
+        die(&format!("every cell mentioned has to be below --cells {}", cells));
+    }
+
+    let spec = Spec {
+        relation,
+        cells,
+        out,
+        zero,
+        keep,
+        truthy,
+        end,
+        steps,
+        cases: cases(relation, range.0, range.1),
+    };
+
+    eprintln!(
+        "searching for {} over {} cases with {} threads",
+        spec.relation.name,
+        spec.cases.len(),
+        threads
+    );
+
+    match shortest(&spec, max_len, threads) {
+        Some(code) => println!("{}", String::from_utf8_lossy(&code)),
+        None => {
+            eprintln!("bfexec: nothing up to {} instructions", max_len);
+            std::process::exit(1);
+        }
+    }
+}
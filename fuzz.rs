@@ -0,0 +1,753 @@
+// Differential fuzzing. Random little c programs get built natively with
+// clang and through bfcc, then both run. Anything where the brainfuck prints
+// something else, faults, leaves the tape dirty or doesn't compile at all
+// gets shrunk down to as little as still breaks the same way and written out
+// as a new tests/cases file.
+//
+// The programs stick to what bfcc is meant to handle: unsigned char
+// variables with every result fitting in a cell before it's even truncated,
+// division and shifts kept defined, and loops with fixed trip counts so
+// everything halts. Output is putchar, which a tiny shim stands in for
+// natively.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::Command;
+use std::time;
+
+use serde_json;
+
+use bfcc;
+use driver;
+use interp::{Config, Error, Fault, Machine, Program};
+
+const USAGE: &str = "usage: verify --fuzz [options]
+
+builds random c programs natively and through bfcc and compares what they
+print and that the tape ends up zeroed. Failures get shrunk and saved as
+tests/cases/fuzz_<seed>.c.
+
+options:
+  --count <n>   programs to try (default 100)
+  --seed <n>    seed of the first program, the rest count up from it
+                (default: the time)
+  -O<n>         only build at this opt level instead of -O0 and -O1
+  -h, --help    print this";
+
+// bfcc builds putchar in, natively it's this
+const SHIM: &str = "long write(int fd, const void *buf, unsigned long n);
+
+void putchar(unsigned char c) { write(1, &c, 1); }
+";
+
+const OPS: &[&str] = &[
+	"+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "<", "<=", ">", ">=",
+	"==", "!=",
+];
+
+// xorshift64*, plenty for picking statements
+struct Rng(u64);
+
+impl Rng {
+	fn new(seed: u64) -> Rng {
+		// 0 would stay 0 forever
+		Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+	}
+
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		(self.next() % n as u64) as usize
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+	Const(u8),
+	Var(usize),
+	// a helper's own a or b
+	Param(usize),
+	Bin(&'static str, Box<Expr>, Box<Expr>),
+	Call(usize, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+	Assign(usize, Expr),
+	Print(Expr),
+	If(Expr, Vec<Stmt>, Vec<Stmt>),
+	// the body a fixed number of times
+	Loop(u8, Vec<Stmt>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Case {
+	// uint8_t fN(uint8_t a, uint8_t b) { return <expr>; }
+	helpers: Vec<Expr>,
+	// what each variable starts out as
+	vars: Vec<u8>,
+	body: Vec<Stmt>,
+}
+
+impl fmt::Display for Expr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Expr::Const(c) => write!(f, "{}", c),
+			Expr::Var(v) => write!(f, "v{}", v),
+			Expr::Param(p) => write!(f, "{}", ["a", "b"][*p]),
+			Expr::Bin(op, l, r) => match *op {
+				"/" | "%" => write!(f, "(uint8_t)({} {} ({} | 1))", l, op, r),
+				"<<" | ">>" => write!(f, "(uint8_t)({} {} ({} & 7))", l, op, r),
+				"+" | "-" | "*" | "&" | "|" | "^" => {
+					write!(f, "(uint8_t)({} {} {})", l, op, r)
+				}
+				_ => write!(f, "({} {} {})", l, op, r),
+			},
+			Expr::Call(h, a, b) => write!(f, "f{}({}, {})", h, a, b),
+		}
+	}
+}
+
+fn render(out: &mut String, stmts: &[Stmt], depth: usize) {
+	let pad = "  ".repeat(depth + 1);
+	for s in stmts {
+		match s {
+			Stmt::Assign(v, e) => {
+				out.push_str(&format!("{}v{} = {};\n", pad, v, e))
+			}
+			Stmt::Print(e) => out.push_str(&format!("{}putchar({});\n", pad, e)),
+			Stmt::If(c, t, e) => {
+				out.push_str(&format!("{}if ({}) {{\n", pad, c));
+				render(out, t, depth + 1);
+				if !e.is_empty() {
+					out.push_str(&format!("{}}} else {{\n", pad));
+					render(out, e, depth + 1);
+				}
+				out.push_str(&format!("{}}}\n", pad));
+			}
+			Stmt::Loop(n, body) => {
+				out.push_str(&format!(
+					"{}for (uint8_t i{d} = 0; i{d} < {}; i{d}++) {{\n",
+					pad,
+					n,
+					d = depth
+				));
+				render(out, body, depth + 1);
+				out.push_str(&format!("{}}}\n", pad));
+			}
+		}
+	}
+}
+
+impl Case {
+	// header goes right above main, where the TEST: line lives
+	fn source(&self, header: &str) -> String {
+		let mut out = "#include \"stdfuck.h\"\n\n".to_string();
+		for (i, h) in self.helpers.iter().enumerate() {
+			out.push_str(&format!(
+				"uint8_t f{}(uint8_t a, uint8_t b) {{\n  return {};\n}}\n\n",
+				i, h
+			));
+		}
+		out.push_str(header);
+		out.push_str("int main() {\n");
+		for (i, v) in self.vars.iter().enumerate() {
+			out.push_str(&format!("  uint8_t v{} = {};\n", i, v));
+		}
+		render(&mut out, &self.body, 0);
+		out.push_str("  return 0;\n}\n");
+		out
+	}
+}
+
+struct Gen {
+	rng: Rng,
+	vars: usize,
+	helpers: usize,
+}
+
+impl Gen {
+	fn expr(&mut self, depth: usize, params: bool) -> Expr {
+		if depth == 0 || self.rng.below(4) == 0 {
+			return match self.rng.below(3) {
+				0 => Expr::Const(self.rng.next() as u8),
+				_ if params => Expr::Param(self.rng.below(2)),
+				_ => Expr::Var(self.rng.below(self.vars)),
+			};
+		}
+
+		let l = Box::new(self.expr(depth - 1, params));
+		let r = Box::new(self.expr(depth - 1, params));
+		if !params && self.helpers > 0 && self.rng.below(6) == 0 {
+			Expr::Call(self.rng.below(self.helpers), l, r)
+		} else {
+			Expr::Bin(OPS[self.rng.below(OPS.len())], l, r)
+		}
+	}
+
+	fn block(&mut self, depth: usize) -> Vec<Stmt> {
+		let n = 1 + self.rng.below(if depth == 0 { 8 } else { 4 });
+		(0..n).map(|_| self.stmt(depth)).collect()
+	}
+
+	fn stmt(&mut self, depth: usize) -> Stmt {
+		match self.rng.below(10) {
+			6 | 7 if depth < 2 => {
+				let c = self.expr(2, false);
+				let t = self.block(depth + 1);
+				let e = if self.rng.below(2) == 0 {
+					self.block(depth + 1)
+				} else {
+					vec![]
+				};
+				Stmt::If(c, t, e)
+			}
+			8 if depth < 2 => {
+				let n = 1 + self.rng.below(5) as u8;
+				Stmt::Loop(n, self.block(depth + 1))
+			}
+			4 | 5 => Stmt::Print(self.expr(2, false)),
+			_ => {
+				let v = self.rng.below(self.vars);
+				Stmt::Assign(v, self.expr(3, false))
+			}
+		}
+	}
+}
+
+fn generate(seed: u64) -> Case {
+	let mut rng = Rng::new(seed);
+	let vars = 2 + rng.below(4);
+	let helpers = rng.below(3);
+	let mut gen = Gen {
+		rng: rng,
+		vars: vars,
+		helpers: helpers,
+	};
+
+	let helpers = (0..helpers).map(|_| gen.expr(3, true)).collect();
+	let vars = (0..vars).map(|_| gen.rng.next() as u8).collect();
+	let mut body = gen.block(0);
+	// everything ends up printed so nothing goes wrong quietly
+	body.extend((0..gen.vars).map(|v| Stmt::Print(Expr::Var(v))));
+
+	let mut case = Case {
+		helpers: helpers,
+		vars: vars,
+		body: body,
+	};
+	// each round takes out one op that would leave a cell
+	while !fits_cells(&mut case, true) {}
+	case
+}
+
+// Smaller versions of a case, roughly biggest cuts first.
+fn shrink(case: &Case) -> Vec<Case> {
+	let mut out = vec![];
+
+	for body in shrink_block(&case.body) {
+		out.push(Case {
+			body: body,
+			..case.clone()
+		});
+	}
+
+	// helpers and variables can only go from the end without renumbering
+	// the rest
+	if let Some(last) = case.helpers.len().checked_sub(1) {
+		let calls = |e: &Expr| match e {
+			Expr::Call(h, _, _) => *h == last,
+			_ => false,
+		};
+		if !case.body.iter().any(|s| stmt_any(s, &calls)) {
+			let mut c = case.clone();
+			c.helpers.pop();
+			out.push(c);
+		}
+	}
+	if let Some(last) = case.vars.len().checked_sub(1) {
+		let uses = |e: &Expr| *e == Expr::Var(last);
+		if !case.body.iter().any(|s| stmt_any(s, &uses)) {
+			let mut c = case.clone();
+			c.vars.pop();
+			out.push(c);
+		}
+	}
+
+	for (i, h) in case.helpers.iter().enumerate() {
+		for e in shrink_expr(h) {
+			let mut c = case.clone();
+			c.helpers[i] = e;
+			out.push(c);
+		}
+	}
+
+	for (i, v) in case.vars.iter().enumerate() {
+		if *v != 0 {
+			let mut c = case.clone();
+			c.vars[i] = 0;
+			out.push(c);
+		}
+	}
+
+	out
+}
+
+fn shrink_block(block: &[Stmt]) -> Vec<Vec<Stmt>> {
+	// block with stmt i swapped for mid
+	let with = |i: usize, mid: &[Stmt]| -> Vec<Stmt> {
+		block[..i]
+			.iter()
+			.chain(mid.iter())
+			.chain(block[i + 1..].iter())
+			.cloned()
+			.collect()
+	};
+
+	let mut out: Vec<Vec<Stmt>> = (0..block.len()).map(|i| with(i, &[])).collect();
+	for (i, s) in block.iter().enumerate() {
+		match s {
+			Stmt::If(_, t, e) => {
+				out.push(with(i, t));
+				out.push(with(i, e));
+			}
+			Stmt::Loop(_, body) => out.push(with(i, body)),
+			_ => {}
+		}
+		for s in shrink_stmt(s) {
+			out.push(with(i, &[s]));
+		}
+	}
+	out
+}
+
+fn shrink_stmt(s: &Stmt) -> Vec<Stmt> {
+	match s {
+		Stmt::Assign(v, e) => shrink_expr(e)
+			.into_iter()
+			.map(|e| Stmt::Assign(*v, e))
+			.collect(),
+		Stmt::Print(e) => shrink_expr(e).into_iter().map(Stmt::Print).collect(),
+		Stmt::If(c, t, e) => {
+			let mut out: Vec<Stmt> = shrink_expr(c)
+				.into_iter()
+				.map(|c| Stmt::If(c, t.clone(), e.clone()))
+				.collect();
+			for t in shrink_block(t) {
+				out.push(Stmt::If(c.clone(), t, e.clone()));
+			}
+			for e in shrink_block(e) {
+				out.push(Stmt::If(c.clone(), t.clone(), e));
+			}
+			out
+		}
+		Stmt::Loop(n, body) => {
+			let mut out = vec![];
+			if *n > 1 {
+				out.push(Stmt::Loop(1, body.clone()));
+			}
+			for b in shrink_block(body) {
+				out.push(Stmt::Loop(*n, b));
+			}
+			out
+		}
+	}
+}
+
+fn shrink_expr(e: &Expr) -> Vec<Expr> {
+	let mut out = vec![];
+	match e {
+		Expr::Const(0) => {}
+		Expr::Const(_) | Expr::Var(_) | Expr::Param(_) => out.push(Expr::Const(0)),
+		Expr::Bin(op, l, r) => {
+			out.push((**l).clone());
+			out.push((**r).clone());
+			out.push(Expr::Const(0));
+			for s in shrink_expr(l) {
+				out.push(Expr::Bin(op, Box::new(s), r.clone()));
+			}
+			for s in shrink_expr(r) {
+				out.push(Expr::Bin(op, l.clone(), Box::new(s)));
+			}
+		}
+		Expr::Call(h, a, b) => {
+			out.push((**a).clone());
+			out.push((**b).clone());
+			out.push(Expr::Const(0));
+			for s in shrink_expr(a) {
+				out.push(Expr::Call(*h, Box::new(s), b.clone()));
+			}
+			for s in shrink_expr(b) {
+				out.push(Expr::Call(*h, a.clone(), Box::new(s)));
+			}
+		}
+	}
+	out
+}
+
+// whether f holds anywhere in e
+fn expr_any(e: &Expr, f: &dyn Fn(&Expr) -> bool) -> bool {
+	f(e) || match e {
+		Expr::Bin(_, l, r) | Expr::Call(_, l, r) => {
+			expr_any(l, f) || expr_any(r, f)
+		}
+		_ => false,
+	}
+}
+
+// same for every expression in s, counting what it assigns to as a Var
+fn stmt_any(s: &Stmt, f: &dyn Fn(&Expr) -> bool) -> bool {
+	match s {
+		Stmt::Assign(v, e) => f(&Expr::Var(*v)) || expr_any(e, f),
+		Stmt::Print(e) => expr_any(e, f),
+		Stmt::If(c, t, e) => {
+			expr_any(c, f)
+				|| t.iter().any(|s| stmt_any(s, f))
+				|| e.iter().any(|s| stmt_any(s, f))
+		}
+		Stmt::Loop(_, body) => body.iter().any(|s| stmt_any(s, f)),
+	}
+}
+
+// Runs a case the way c would, ints and all. c does +, -, * and << in int
+// and the (uint8_t) wraps it back, but bfcc keeps every int in one cell and
+// faults the moment it leaves 0..=255. That's the promise working, not a
+// bug, so those cases are no use to us. Says false at the first one, and
+// with fix turns it into a ^ on the way out.
+fn fits_cells(case: &mut Case, fix: bool) -> bool {
+	let mut vars = case.vars.clone();
+	exec(&mut case.body, &mut case.helpers, &mut vars, fix).is_some()
+}
+
+fn exec(
+	block: &mut [Stmt],
+	helpers: &mut [Expr],
+	vars: &mut [u8],
+	fix: bool,
+) -> Option<()> {
+	for s in block.iter_mut() {
+		match s {
+			Stmt::Assign(v, e) => {
+				let x = eval(e, helpers, vars, [0, 0], fix)?;
+				vars[*v] = x;
+			}
+			Stmt::Print(e) => {
+				eval(e, helpers, vars, [0, 0], fix)?;
+			}
+			Stmt::If(c, t, e) => {
+				if eval(c, helpers, vars, [0, 0], fix)? != 0 {
+					exec(t, helpers, vars, fix)?;
+				} else {
+					exec(e, helpers, vars, fix)?;
+				}
+			}
+			Stmt::Loop(n, body) => {
+				for _ in 0..*n {
+					exec(body, helpers, vars, fix)?;
+				}
+			}
+		}
+	}
+	Some(())
+}
+
+fn eval(
+	e: &mut Expr,
+	helpers: &mut [Expr],
+	vars: &[u8],
+	params: [u8; 2],
+	fix: bool,
+) -> Option<u8> {
+	let (op, l, r) = match e {
+		Expr::Const(c) => return Some(*c),
+		Expr::Var(v) => return Some(vars[*v]),
+		Expr::Param(p) => return Some(params[*p]),
+		Expr::Call(h, a, b) => {
+			let args = [
+				eval(a, helpers, vars, params, fix)?,
+				eval(b, helpers, vars, params, fix)?,
+			];
+			// helpers never call anything themselves
+			let mut body = helpers[*h].clone();
+			let ret = eval(&mut body, &mut [], vars, args, fix);
+			helpers[*h] = body;
+			return ret;
+		}
+		Expr::Bin(op, l, r) => (op, l, r),
+	};
+
+	let l = i32::from(eval(l, helpers, vars, params, fix)?);
+	let r = i32::from(eval(r, helpers, vars, params, fix)?);
+	let v = match *op {
+		"+" => l + r,
+		"-" => l - r,
+		"*" => l * r,
+		"/" => l / (r | 1),
+		"%" => l % (r | 1),
+		"&" => l & r,
+		"|" => l | r,
+		"^" => l ^ r,
+		"<<" => l << (r & 7),
+		">>" => l >> (r & 7),
+		"<" => i32::from(l < r),
+		"<=" => i32::from(l <= r),
+		">" => i32::from(l > r),
+		">=" => i32::from(l >= r),
+		"==" => i32::from(l == r),
+		"!=" => i32::from(l != r),
+		_ => unreachable!("op {}", op),
+	};
+	if !(0..=255).contains(&v) {
+		if fix {
+			*op = "^";
+		}
+		return None;
+	}
+	Some(v as u8)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+	// clang or bfcc gave up, or bfcc panicked
+	Compile,
+	Fault(Error),
+	Mismatch,
+}
+
+struct Failure {
+	kind: Kind,
+	detail: String,
+}
+
+fn printable(out: &[u8]) -> String {
+	out.iter().map(|c| *c as char).collect()
+}
+
+struct Fuzzer {
+	workdir: PathBuf,
+	shim: PathBuf,
+	libs: Vec<PathBuf>,
+	config: Config,
+}
+
+impl Fuzzer {
+	// What the case prints built natively. Not getting that far is on the
+	// generator, not bfcc.
+	fn native(&self, src: &Path) -> Result<Vec<u8>, String> {
+		let bin = self.workdir.join("native");
+		driver::clang_native(
+			&["-w", "-fno-builtin", "-O0"],
+			&[src.to_path_buf(), self.shim.clone()],
+			&bin,
+		)?;
+
+		let out = Command::new(&bin)
+			.output()
+			.map_err(|e| format!("{}: {}", bin.display(), e))?;
+		if !out.status.success() {
+			return Err(format!("native build exited with {}", out.status));
+		}
+		Ok(out.stdout)
+	}
+
+	fn brainfuck(&self, src: &Path, opt: &str) -> Result<Vec<u8>, Failure> {
+		let compile = |detail: String| Failure {
+			kind: Kind::Compile,
+			detail: detail,
+		};
+
+		let bc = self.workdir.join("case.bc");
+		driver::clang_bc(&[opt], src, &bc).map_err(&compile)?;
+
+		let opts = bfcc::Options {
+			libs: self.libs.clone(),
+			..bfcc::Options::default()
+		};
+
+		// a panic in bfcc is just another finding
+		let hook = panic::take_hook();
+		panic::set_hook(Box::new(|_| {}));
+		let compiled = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+			bfcc::compile_with(&[bc.clone()], &opts)
+		}));
+		panic::set_hook(hook);
+
+		let code = match compiled {
+			Ok(Ok(c)) => c.code,
+			Ok(Err(e)) => return Err(compile(e)),
+			Err(p) => {
+				let msg = ::panic_message(&p);
+				return Err(compile(format!("bfcc panicked: {}", msg)));
+			}
+		};
+
+		let fault = |f: Fault| Failure {
+			kind: Kind::Fault(f.err),
+			detail: f.to_string(),
+		};
+		let mut machine =
+			Machine::new(Program::new(&code).map_err(&fault)?, self.config.clone());
+		machine.run().map_err(&fault)?;
+		Ok(machine.output)
+	}
+
+	// Err when the native side couldn't say what's right
+	fn check(&self, case: &Case, opt: &str) -> Result<Option<Failure>, String> {
+		let src = self.workdir.join("case.c");
+		fs::write(&src, case.source(""))
+			.map_err(|e| format!("{}: {}", src.display(), e))?;
+
+		let want = self.native(&src)?;
+		Ok(match self.brainfuck(&src, opt) {
+			Err(f) => Some(f),
+			Ok(ref got) if *got != want => Some(Failure {
+				kind: Kind::Mismatch,
+				detail: format!(
+					"expected {:?}, got {:?}",
+					printable(&want),
+					printable(got)
+				),
+			}),
+			Ok(_) => None,
+		})
+	}
+
+	// Greedily take whichever smaller case still fails the same way until
+	// none do.
+	fn minimize(&self, mut case: Case, mut failure: Failure, opt: &str) -> (Case, Failure) {
+		'shrink: loop {
+			for smaller in shrink(&case) {
+				// zeroing things out easily makes a 0 - x
+				if !fits_cells(&mut smaller.clone(), false) {
+					continue;
+				}
+				if let Ok(Some(f)) = self.check(&smaller, opt) {
+					// a different panic is a different bug
+					if f.kind != failure.kind
+						|| (f.kind == Kind::Compile && f.detail != failure.detail)
+					{
+						continue;
+					}
+					case = smaller;
+					failure = f;
+					print!(".");
+					io::stdout().flush().ok();
+					continue 'shrink;
+				}
+			}
+			return (case, failure);
+		}
+	}
+}
+
+pub fn main(args: &[String], libs: &[PathBuf], config: Config) {
+	let die = |msg: &str| -> ! {
+		eprintln!("verify: {}", msg);
+		process::exit(2);
+	};
+
+	let mut count = 100;
+	let mut seed = time::SystemTime::now()
+		.duration_since(time::UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or(0);
+	let mut opts = vec!["-O0".to_string(), "-O1".to_string()];
+
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| -> u64 {
+			let v = args
+				.next()
+				.unwrap_or_else(|| die(&format!("{} expects a value", name)));
+			v.parse()
+				.unwrap_or_else(|_| die(&format!("{} expects a number, got {}", name, v)))
+		};
+
+		match arg.as_str() {
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return;
+			}
+			"--count" => count = value("--count"),
+			"--seed" => seed = value("--seed"),
+			a if a.starts_with("-O") => opts = vec![a.to_string()],
+			a => die(&format!("unknown option {}\n{}", a, USAGE)),
+		}
+	}
+
+	let workdir = ::std::env::temp_dir().join(format!("bffuzz-{}", process::id()));
+	fs::create_dir_all(&workdir)
+		.unwrap_or_else(|e| die(&format!("{}: {}", workdir.display(), e)));
+	let shim = workdir.join("shim.c");
+	fs::write(&shim, SHIM).unwrap_or_else(|e| die(&format!("{}: {}", shim.display(), e)));
+
+	let fuzzer = Fuzzer {
+		workdir: workdir.clone(),
+		shim: shim,
+		libs: libs.to_vec(),
+		config: Config {
+			// bfcc output spins forever when it's wrong often enough
+			steps: Some(500_000_000),
+			..config
+		},
+	};
+
+	let mut failures = 0;
+	for seed in seed..seed + count {
+		let case = generate(seed);
+		print!("\rfuzz {}", seed);
+		io::stdout().flush().ok();
+
+		for opt in opts.iter() {
+			let failure = match fuzzer.check(&case, opt) {
+				Ok(Some(f)) => f,
+				Ok(None) => continue,
+				Err(e) => {
+					println!("\ncouldn't build seed {} natively: {}", seed, e);
+					break;
+				}
+			};
+
+			failures += 1;
+			println!("\n{} {}: {}", seed, opt, failure.detail);
+			print!("shrinking");
+			io::stdout().flush().ok();
+			let (small, failure) = fuzzer.minimize(case.clone(), failure, opt);
+
+			let path = format!("./tests/cases/fuzz_{}.c", seed);
+			let src = workdir.join("case.c");
+			fs::write(&src, small.source("")).ok();
+			let want = fuzzer.native(&src).unwrap_or_default();
+			let header = format!(
+				"// found by verify --fuzz --seed {} at {}: {}\n// TEST:{{ \"name\": \"fuzz {}\", \"output\": {} }}\n",
+				seed,
+				opt,
+				failure.detail.replace("\n", " "),
+				seed,
+				serde_json::to_string(&printable(&want)).unwrap()
+			);
+			fs::write(&path, small.source(&header))
+				.unwrap_or_else(|e| die(&format!("{}: {}", path, e)));
+			println!("\nsaved {}", path);
+
+			// it's a test at every opt level now
+			break;
+		}
+	}
+
+	fs::remove_dir_all(&workdir).ok();
+	println!("\r{} programs, {} failures", count, failures);
+	if failures > 0 {
+		process::exit(1);
+	}
+}
@@ -0,0 +1,801 @@
+use std::fmt;
+use std::io::Read;
+use std::io::Write;
+
+// The brainfuck interpreter every bin runs programs with, so verify, bfexec
+// and bfdb all agree on what a program does and on which of the README's
+// rules it broke. Strict by default: cells don't wrap, the head never leaves
+// the tape.
+
+const INSTRUCTIONS: &[u8] = b"+-<>[].,";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	// + on a cell already at its biggest value
+	Overflow,
+	// - on a 0
+	Underflow,
+	// > off the end of the tape
+	PastEnd,
+	// < off the first cell
+	BeforeStart,
+	UnmatchedOpen,
+	UnmatchedClose,
+	// bfcc only transpiles, it never runs anything into these
+	#[allow(dead_code)]
+	StepLimit,
+	// halted without zeroing the tape again, only when Config::clean_exit
+	#[allow(dead_code)]
+	Dirty,
+}
+
+impl Error {
+	// what bfexec and transpiled programs exit with
+	#[allow(dead_code)]
+	pub fn status(&self) -> i32 {
+		match self {
+			Error::Overflow => 10,
+			Error::Underflow => 11,
+			Error::PastEnd => 12,
+			Error::BeforeStart => 13,
+			Error::UnmatchedOpen | Error::UnmatchedClose => 14,
+			Error::StepLimit => 15,
+			// nothing but verify asks for a clean exit
+			Error::Dirty => 1,
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				Error::Overflow => "cell overflow",
+				Error::Underflow => "cell underflow",
+				Error::PastEnd => "seek past the end of the tape",
+				Error::BeforeStart => "seek left of the first cell",
+				Error::UnmatchedOpen => "unmatched [",
+				Error::UnmatchedClose => "unmatched ]",
+				Error::StepLimit => "step limit",
+				Error::Dirty => "tape not zeroed at exit",
+			}
+		)
+	}
+}
+
+// What went wrong and where. pc counts instructions only, at is the byte
+// offset of that instruction in the source text.
+#[derive(Debug, Clone)]
+pub struct Fault {
+	pub err: Error,
+	pub pc: usize,
+	pub at: usize,
+	pub mp: usize,
+	pub steps: u64,
+	// the unchecked jit doesn't know which instruction it went wrong at or
+	// count steps, so pc, at and steps mean nothing when this is false
+	pub tracked: bool,
+}
+
+impl fmt::Display for Fault {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if !self.tracked {
+			return write!(f, "{} at cell {}", self.err, self.mp);
+		}
+
+		write!(
+			f,
+			"{} at instruction {} (byte {}), cell {}, after {} steps",
+			self.err, self.pc, self.at, self.mp, self.steps
+		)
+	}
+}
+
+// not every binary sets or looks at all of it
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Config {
+	// most cells the program may use, the tape only grows as it's touched
+	pub tape: usize,
+	// 8, 16 or 32
+	pub cell_bits: u32,
+	// cells wrap around instead of over/underflow being an error
+	pub wrap: bool,
+	// give up after this many instructions
+	pub steps: Option<u64>,
+	// every cell has to be 0 again at exit, like bfcc's output promises
+	pub clean_exit: bool,
+	// let run() do common loops in one go, same results either way
+	pub optimize: bool,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			tape: 30000,
+			cell_bits: 8,
+			wrap: false,
+			steps: None,
+			clean_exit: false,
+			optimize: true,
+		}
+	}
+}
+
+// Just the instructions out of some source text, where each one came from and
+// the bracket pairs, checked once up front.
+#[derive(Debug, Clone)]
+pub struct Program {
+	pub code: Vec<u8>,
+	pub at: Vec<usize>,
+	jumps: Vec<usize>,
+	// brackets still waiting for their ], kept around between parses
+	open: Vec<usize>,
+}
+
+impl Program {
+	pub fn new(text: &str) -> Result<Program, Fault> {
+		let mut prog = Program {
+			code: vec![],
+			at: vec![],
+			jumps: vec![],
+			open: vec![],
+		};
+		prog.parse(text.as_bytes())?;
+		Ok(prog)
+	}
+
+	// Replace the program with whatever's in text, reusing the buffers since
+	// the search parses millions of tiny programs.
+	pub fn parse(&mut self, text: &[u8]) -> Result<(), Fault> {
+		self.code.clear();
+		self.at.clear();
+		self.jumps.clear();
+		self.open.clear();
+
+		for (i, &c) in text.iter().enumerate() {
+			if INSTRUCTIONS.contains(&c) {
+				self.code.push(c);
+				self.at.push(i);
+			}
+		}
+
+		let fault = |err, pc: usize, at: &[usize]| Fault {
+			err,
+			pc,
+			at: at[pc],
+			mp: 0,
+			steps: 0,
+			tracked: true,
+		};
+
+		self.jumps.resize(self.code.len(), 0);
+		for (pc, c) in self.code.iter().enumerate() {
+			match c {
+				b'[' => self.open.push(pc),
+				b']' => {
+					let to = match self.open.pop() {
+						Some(to) => to,
+						None => return Err(fault(Error::UnmatchedClose, pc, &self.at)),
+					};
+					self.jumps[to] = pc;
+					self.jumps[pc] = to;
+				}
+				_ => {}
+			}
+		}
+
+		if let Some(pc) = self.open.pop() {
+			return Err(fault(Error::UnmatchedOpen, pc, &self.at));
+		}
+
+		Ok(())
+	}
+}
+
+// bfcc shares the module for transpiling and never makes one of these
+#[allow(dead_code)]
+pub struct Machine {
+	pub prog: Program,
+	pub config: Config,
+	max: u32,
+
+	pub mem: Vec<u32>,
+	pub mp: usize,
+	// next instruction to run
+	pub pc: usize,
+	pub steps: u64,
+
+	// everything read so far, `,` past the end pulls more from stream if
+	// there is one and reads a 0 otherwise
+	pub input: Vec<u8>,
+	pub ic: usize,
+	stream: Option<Box<dyn Read>>,
+
+	// what `.` wrote, unless it's going to sink instead
+	pub output: Vec<u8>,
+	sink: Option<Box<dyn Write>>,
+
+	// prog as fused ops, built the first time run() wants it
+	fast: Option<Fast>,
+}
+
+// each binary drives it its own way, none of them use every method
+#[allow(dead_code)]
+impl Machine {
+	pub fn new(prog: Program, config: Config) -> Machine {
+		let max = match config.cell_bits {
+			32 => u32::MAX,
+			b => (1 << b) - 1,
+		};
+
+		Machine {
+			prog,
+			config,
+			max,
+
+			mem: vec![0],
+			mp: 0,
+			pc: 0,
+			steps: 0,
+
+			input: vec![],
+			ic: 0,
+			stream: None,
+
+			output: vec![],
+			sink: None,
+
+			fast: None,
+		}
+	}
+
+	pub fn with_input(mut self, input: &[u8]) -> Machine {
+		self.input = input.to_vec();
+		self
+	}
+
+	pub fn input_from(mut self, stream: Box<dyn Read>) -> Machine {
+		self.stream = Some(stream);
+		self
+	}
+
+	pub fn output_to(mut self, sink: Box<dyn Write>) -> Machine {
+		self.sink = Some(sink);
+		self
+	}
+
+	// Back to the first instruction with a blank tape, for running the same
+	// program again. Input starts over from the beginning too.
+	pub fn reset(&mut self) {
+		self.mem.clear();
+		self.mem.push(0);
+		self.mp = 0;
+		self.pc = 0;
+		self.steps = 0;
+		self.ic = 0;
+		self.output.clear();
+	}
+
+	// Parse text over the current program and reset, keeping every
+	// allocation.
+	pub fn load(&mut self, text: &[u8]) -> Result<(), Fault> {
+		self.fast = None;
+		self.prog.parse(text)?;
+		self.reset();
+		Ok(())
+	}
+
+	pub fn halted(&self) -> bool {
+		self.pc >= self.prog.code.len()
+	}
+
+	// 0 for anything the program hasn't touched yet
+	pub fn cell(&self, i: usize) -> u32 {
+		self.mem.get(i).cloned().unwrap_or(0)
+	}
+
+	pub fn poke(&mut self, i: usize, v: u32) {
+		if i >= self.mem.len() {
+			self.mem.resize(i + 1, 0);
+		}
+		self.mem[i] = v & self.max;
+	}
+
+	fn fault(&self, err: Error) -> Fault {
+		Fault {
+			err,
+			pc: self.pc,
+			at: self.prog.at.get(self.pc).cloned().unwrap_or(0),
+			mp: self.mp,
+			steps: self.steps,
+			tracked: true,
+		}
+	}
+
+	// what `,` reads
+	pub fn getch(&mut self) -> u8 {
+		if self.ic == self.input.len() {
+			let mut b = [0];
+			match self.stream.as_mut().map(|s| s.read(&mut b)) {
+				Some(Ok(1)) => self.input.push(b[0]),
+				_ => return 0,
+			}
+		}
+
+		self.ic += 1;
+		self.input[self.ic - 1]
+	}
+
+	// what `.` writes
+	pub fn putch(&mut self, c: u8) {
+		match self.sink.as_mut() {
+			Some(sink) => {
+				sink.write_all(&[c]).ok();
+			}
+			None => self.output.push(c),
+		}
+	}
+
+	// Run the next instruction, doing nothing once halted.
+	#[inline(always)]
+	pub fn step(&mut self) -> Result<(), Fault> {
+		if self.halted() {
+			return Ok(());
+		}
+
+		if self.config.steps.is_some_and(|s| self.steps >= s) {
+			return Err(self.fault(Error::StepLimit));
+		}
+
+		match self.prog.code[self.pc] {
+			b'+' => {
+				if self.mem[self.mp] == self.max {
+					if !self.config.wrap {
+						return Err(self.fault(Error::Overflow));
+					}
+					self.mem[self.mp] = 0;
+				} else {
+					self.mem[self.mp] += 1;
+				}
+			}
+			b'-' => {
+				if self.mem[self.mp] == 0 {
+					if !self.config.wrap {
+						return Err(self.fault(Error::Underflow));
+					}
+					self.mem[self.mp] = self.max;
+				} else {
+					self.mem[self.mp] -= 1;
+				}
+			}
+			b'>' => {
+				if self.mp + 1 == self.mem.len() {
+					if self.mem.len() >= self.config.tape {
+						return Err(self.fault(Error::PastEnd));
+					}
+					self.mem.push(0);
+				}
+				self.mp += 1;
+			}
+			b'<' => {
+				if self.mp == 0 {
+					return Err(self.fault(Error::BeforeStart));
+				}
+				self.mp -= 1;
+			}
+			b'[' => {
+				if self.mem[self.mp] == 0 {
+					self.pc = self.prog.jumps[self.pc];
+				}
+			}
+			b']' => {
+				if self.mem[self.mp] != 0 {
+					self.pc = self.prog.jumps[self.pc];
+				}
+			}
+			b'.' => {
+				// wider cells only get their low byte written out
+				let c = self.mem[self.mp] as u8;
+				self.putch(c);
+			}
+			b',' => {
+				let c = self.getch();
+				self.mem[self.mp] = c as u32 & self.max;
+			}
+			_ => unreachable!(),
+		}
+
+		self.pc += 1;
+		self.steps += 1;
+
+		Ok(())
+	}
+
+	// Run to the end, calling trace before every instruction. Gives back how
+	// many instructions ran.
+	pub fn run_traced(
+		&mut self,
+		trace: &mut dyn FnMut(&Machine),
+	) -> Result<u64, Fault> {
+		let mut result = Ok(());
+		while !self.halted() && result.is_ok() {
+			trace(self);
+			result = self.step();
+		}
+
+		self.finish(result)
+	}
+
+	pub fn run(&mut self) -> Result<u64, Fault> {
+		if self.config.optimize {
+			let result = self.run_fast();
+			return self.finish(result);
+		}
+
+		// not run_traced with a no-op, going through the dyn call every
+		// instruction is most of the time for short programs
+		let mut result = Ok(());
+		while !self.halted() && result.is_ok() {
+			result = self.step();
+		}
+
+		self.finish(result)
+	}
+
+	// Whether cost more instructions would go over the step limit.
+	fn over_budget(&self, cost: u64) -> bool {
+		self.config.steps.is_some_and(|s| self.steps + cost > s)
+	}
+
+	// Step through instructions until pc gets to end, the slow way. Fused ops
+	// fall back to this whenever they'd break a rule, so faults come out at
+	// exactly the instruction and step they would have one at a time.
+	fn step_to(&mut self, end: usize) -> Result<(), Fault> {
+		while self.pc != end {
+			self.step()?;
+		}
+		Ok(())
+	}
+
+	fn grow(&mut self, cell: usize) {
+		if cell >= self.mem.len() {
+			self.mem.resize(cell + 1, 0);
+		}
+	}
+
+	fn run_fast(&mut self) -> Result<(), Fault> {
+		let fast = match self.fast.take() {
+			Some(fast) => fast,
+			None => Fast::new(&self.prog),
+		};
+		let result = self.run_ops(&fast);
+		self.fast = Some(fast);
+		result
+	}
+
+	fn run_ops(&mut self, fast: &Fast) -> Result<(), Fault> {
+		// get to the start of an op first if something stepped us into the
+		// middle of one
+		let mut i = loop {
+			match fast.pcs.binary_search(&self.pc) {
+				Ok(i) => break i,
+				Err(_) => self.step()?,
+			}
+		};
+
+		let wrap = self.config.wrap;
+		let modulus = self.max as u64 + 1;
+
+		while i < fast.ops.len() {
+			let (start, end) = (fast.pcs[i], fast.pcs[i + 1]);
+			let mp = self.mp;
+
+			match fast.ops[i] {
+				FastOp::Add(n) => {
+					let v = self.mem[mp] as u64 + n as u64;
+					if v > self.max as u64 && !wrap || self.over_budget(n as u64) {
+						self.pc = start;
+						self.step_to(end)?;
+					} else {
+						self.mem[mp] = (v % modulus) as u32;
+						self.steps += n as u64;
+					}
+				}
+				FastOp::Sub(n) => {
+					let v = self.mem[mp] as u64;
+					if v < n as u64 && !wrap || self.over_budget(n as u64) {
+						self.pc = start;
+						self.step_to(end)?;
+					} else {
+						self.mem[mp] = ((v + modulus - n as u64 % modulus) % modulus) as u32;
+						self.steps += n as u64;
+					}
+				}
+				FastOp::Right(n) => {
+					if mp + n >= self.config.tape || self.over_budget(n as u64) {
+						self.pc = start;
+						self.step_to(end)?;
+					} else {
+						self.mp += n;
+						self.grow(self.mp);
+						self.steps += n as u64;
+					}
+				}
+				FastOp::Left(n) => {
+					if mp < n || self.over_budget(n as u64) {
+						self.pc = start;
+						self.step_to(end)?;
+					} else {
+						self.mp -= n;
+						self.steps += n as u64;
+					}
+				}
+				FastOp::Open(close) => {
+					if self.over_budget(1) {
+						self.pc = start;
+						return self.step().map(|_| ());
+					}
+					self.steps += 1;
+					if self.mem[mp] == 0 {
+						i = close;
+					}
+				}
+				FastOp::Close(open) => {
+					if self.over_budget(1) {
+						self.pc = start;
+						return self.step().map(|_| ());
+					}
+					self.steps += 1;
+					if self.mem[mp] != 0 {
+						i = open;
+					}
+				}
+				FastOp::Io => {
+					self.pc = start;
+					self.step()?;
+				}
+				FastOp::Transfer(t) => {
+					let t = &fast.transfers[t];
+					let v = self.mem[mp] as u64;
+					let cost = 1 + v * (t.body + 1);
+
+					let lo = mp as isize + t.lo;
+					let hi = mp as isize + t.hi;
+					let fits = v == 0 || lo >= 0 && (hi as usize) < self.config.tape;
+					if fits && v > 0 {
+						self.grow(hi as usize);
+					}
+
+					// every target has to land inside a cell, otherwise the
+					// slow way finds the exact iteration it breaks on
+					let fine = fits
+						&& !self.over_budget(cost)
+						&& (v == 0
+							|| wrap || t.targets.iter().all(|&(o, k)| {
+								let c = self.mem[(mp as isize + o) as usize] as i64;
+								let to = c + k * v as i64;
+								to >= 0 && to <= self.max as i64
+							}));
+
+					if !fine {
+						self.pc = start;
+						self.step_to(end)?;
+					} else if v > 0 {
+						for &(o, k) in t.targets.iter() {
+							let c = &mut self.mem[(mp as isize + o) as usize];
+							let to = (*c as i64 + k * v as i64).rem_euclid(modulus as i64);
+							*c = to as u32;
+						}
+						self.mem[mp] = 0;
+						self.steps += cost;
+					} else {
+						self.steps += 1;
+					}
+				}
+				FastOp::Scan(k) => {
+					let mut at = mp as isize;
+					let mut hops = 0;
+					let mut fine = true;
+					while self.cell(at as usize) != 0 {
+						at += k;
+						hops += 1;
+						if at < 0 || at as usize >= self.config.tape {
+							fine = false;
+							break;
+						}
+					}
+
+					let cost = 1 + hops * (k.unsigned_abs() as u64 + 1);
+					if !fine || self.over_budget(cost) {
+						self.pc = start;
+						self.step_to(end)?;
+					} else {
+						self.mp = at as usize;
+						self.grow(self.mp);
+						self.steps += cost;
+					}
+				}
+			}
+
+			i += 1;
+		}
+
+		self.pc = self.prog.code.len();
+		Ok(())
+	}
+
+	fn finish(&mut self, result: Result<(), Fault>) -> Result<u64, Fault> {
+		if let Some(sink) = self.sink.as_mut() {
+			sink.flush().ok();
+		}
+		result?;
+
+		if self.config.clean_exit && self.mem.iter().any(|c| *c != 0) {
+			return Err(self.fault(Error::Dirty));
+		}
+
+		Ok(self.steps)
+	}
+}
+
+// The fused form of a program the optimizing interpreter and the jit both
+// run.
+// bfdb steps everything and never fuses
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum FastOp {
+	// runs of the same instruction
+	Add(u32),
+	Sub(u32),
+	Right(usize),
+	Left(usize),
+	// index of the matching bracket op
+	Open(usize),
+	Close(usize),
+	// , and . just get stepped
+	Io,
+	// [-] [->+<] [->>+<<<+>] .. with the details in Fast::transfers
+	Transfer(usize),
+	// [>] [<] [>>] .. stops on the first 0 k cells apart
+	Scan(isize),
+}
+
+// A loop that takes 1 off the cell it starts on each time round and adds a
+// fixed amount to a few others, so it's really just
+// cell[o] += k * cell[0] for each target, then cell[0] = 0.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Transfer {
+	pub targets: Vec<(isize, i64)>,
+	// furthest the body wanders either way
+	pub lo: isize,
+	pub hi: isize,
+	// instructions in the body
+	pub body: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Fast {
+	pub ops: Vec<FastOp>,
+	// instruction each op starts on, plus one past the end
+	pub pcs: Vec<usize>,
+	pub transfers: Vec<Transfer>,
+}
+
+#[allow(dead_code)]
+impl Fast {
+	pub fn new(prog: &Program) -> Fast {
+		let code = &prog.code;
+		let mut fast = Fast {
+			ops: vec![],
+			pcs: vec![],
+			transfers: vec![],
+		};
+		let mut open = vec![];
+
+		let mut pc = 0;
+		while pc < code.len() {
+			let c = code[pc];
+			fast.pcs.push(pc);
+
+			let run = code[pc..].iter().take_while(|&&o| o == c).count();
+
+			let op = match c {
+				b'+' => FastOp::Add(run as u32),
+				b'-' => FastOp::Sub(run as u32),
+				b'>' => FastOp::Right(run),
+				b'<' => FastOp::Left(run),
+				b'[' => {
+					let close = prog.jumps[pc];
+					let body = &code[pc + 1..close];
+
+					if let Some(t) = transfer(body) {
+						fast.transfers.push(t);
+						pc = close + 1;
+						fast.ops.push(FastOp::Transfer(fast.transfers.len() - 1));
+						continue;
+					}
+
+					if !body.is_empty()
+						&& body.iter().all(|&o| o == body[0])
+						&& (body[0] == b'>' || body[0] == b'<')
+					{
+						let k = body.len() as isize;
+						pc = close + 1;
+						fast.ops.push(FastOp::Scan(if body[0] == b'>' { k } else { -k }));
+						continue;
+					}
+
+					open.push(fast.ops.len());
+					FastOp::Open(0)
+				}
+				b']' => {
+					let o = open.pop().unwrap();
+					fast.ops[o] = FastOp::Open(fast.ops.len());
+					FastOp::Close(o)
+				}
+				_ => FastOp::Io,
+			};
+
+			pc += match op {
+				FastOp::Add(_) | FastOp::Sub(_) | FastOp::Right(_) | FastOp::Left(_) => run,
+				_ => 1,
+			};
+			fast.ops.push(op);
+		}
+
+		fast.pcs.push(code.len());
+		fast
+	}
+}
+
+// The Transfer a loop body amounts to, if it is one. Each cell's adds all
+// have to go the same way so the cell only ever moves in one direction and
+// checking where it ends up is as good as checking every step.
+#[allow(dead_code)]
+fn transfer(body: &[u8]) -> Option<Transfer> {
+	let mut at = 0;
+	let mut lo = 0;
+	let mut hi = 0;
+	let mut adds: Vec<(isize, i64)> = vec![];
+
+	for &c in body {
+		match c {
+			b'>' => at += 1,
+			b'<' => at -= 1,
+			b'+' | b'-' => {
+				let d = if c == b'+' { 1 } else { -1 };
+				match adds.iter_mut().find(|(o, _)| *o == at) {
+					Some((_, k)) if (*k > 0) == (d > 0) => *k += d,
+					Some(_) => return None,
+					None => adds.push((at, d)),
+				}
+			}
+			_ => return None,
+		}
+		lo = lo.min(at);
+		hi = hi.max(at);
+	}
+
+	if at != 0 {
+		return None;
+	}
+
+	match adds.iter().position(|&(o, k)| o == 0 && k == -1) {
+		Some(i) => {
+			adds.remove(i);
+		}
+		None => return None,
+	}
+
+	Some(Transfer {
+		targets: adds,
+		lo,
+		hi,
+		body: body.len() as u64,
+	})
+}
@@ -0,0 +1,550 @@
+// Runs a program's fused ops (interp::Fast) as x86-64 machine code instead of
+// interpreting them.
+//
+// Checked code does the same tests the fused interpreter does before each op
+// (cell over/underflow unless wrapping, the ends of the tape, the step limit)
+// and if one fails it leaves, telling us which op. The interpreter picks up
+// from the start of that op and steps it one instruction at a time, so a
+// fault comes out exactly like it would have without the jit. Unchecked code
+// does none of that: cells wrap, steps aren't counted and the tape has guard
+// pages around it so wandering off mostly ends in a segfault rather than
+// scribbling on something.
+//
+// Registers while jitted code runs:
+//   rbx  the current cell
+//   r12  first cell
+//   r13  one past the last cell
+//   r14  steps so far
+//   r15  step limit
+//   rbp  the Ctx
+
+use interp::{Config, Fast, FastOp, Fault, Machine};
+
+pub fn unsupported(config: &Config) -> Option<String> {
+	if !cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+		return Some("the jit only runs on x86-64 linux".to_string());
+	}
+
+	if config.cell_bits != 8 {
+		return Some("the jit only does 8 bit cells".to_string());
+	}
+
+	None
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use self::x86_64::run;
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn run(_: &mut Machine, _: bool) -> Result<u64, Fault> {
+	panic!("check jit::unsupported first")
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod x86_64 {
+	use super::*;
+	use interp::Error;
+	use std::ptr;
+
+	extern "C" {
+		fn mmap(
+			addr: *mut u8,
+			len: usize,
+			prot: i32,
+			flags: i32,
+			fd: i32,
+			off: i64,
+		) -> *mut u8;
+		fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+		fn munmap(addr: *mut u8, len: usize) -> i32;
+	}
+
+	const PROT_NONE: i32 = 0;
+	const PROT_READ: i32 = 1;
+	const PROT_WRITE: i32 = 2;
+	const PROT_EXEC: i32 = 4;
+	const MAP_PRIVATE: i32 = 2;
+	const MAP_ANONYMOUS: i32 = 0x20;
+
+	const PAGE: usize = 4096;
+	// either side of the tape, only matters for unchecked code
+	const GUARD: usize = 1 << 20;
+
+	// Everything the jitted code needs that isn't baked into it. Offsets are
+	// hardcoded in the prologue and epilogue.
+	#[repr(C)]
+	struct Ctx {
+		base: *mut u8,
+		end: *mut u8,
+		cell: *mut u8,
+		steps: u64,
+		limit: u64,
+		machine: *mut Machine,
+		// where to start running
+		entry: *const u8,
+	}
+
+	extern "C" fn putch(ctx: *mut Ctx, c: u32) {
+		unsafe { (*(*ctx).machine).putch(c as u8) }
+	}
+
+	extern "C" fn getch(ctx: *mut Ctx) -> u32 {
+		unsafe { (*(*ctx).machine).getch() as u32 }
+	}
+
+	// A private anonymous mapping, unmapped when dropped.
+	struct Map {
+		addr: *mut u8,
+		len: usize,
+	}
+
+	impl Map {
+		fn new(len: usize, prot: i32) -> Map {
+			let len = len.div_ceil(PAGE) * PAGE;
+			let addr = unsafe {
+				mmap(ptr::null_mut(), len, prot, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0)
+			};
+			if addr as isize == -1 {
+				panic!("jit: mmap of {} bytes failed", len);
+			}
+
+			Map {
+				addr,
+				len,
+			}
+		}
+
+		fn protect(&self, from: usize, len: usize, prot: i32) {
+			if unsafe { mprotect(self.addr.add(from), len, prot) } != 0 {
+				panic!("jit: mprotect failed");
+			}
+		}
+	}
+
+	impl Drop for Map {
+		fn drop(&mut self) {
+			unsafe {
+				munmap(self.addr, self.len);
+			}
+		}
+	}
+
+	struct Asm {
+		code: Vec<u8>,
+		checked: bool,
+		wrap: bool,
+		// rel32s that jump to the epilogue
+		exits: Vec<usize>,
+		// rel32s that jump to the start of an op
+		jumps: Vec<(usize, usize)>,
+	}
+
+	impl Asm {
+		fn emit(&mut self, bytes: &[u8]) {
+			self.code.extend_from_slice(bytes);
+		}
+
+		fn imm32(&mut self, v: i64) {
+			assert!(v >= i32::MIN as i64 && v <= i32::MAX as i64);
+			self.emit(&(v as i32).to_le_bytes());
+		}
+
+		// rel32 placeholder, patched once we know where it goes
+		fn rel32(&mut self) -> usize {
+			self.emit(&[0; 4]);
+			self.code.len() - 4
+		}
+
+		fn patch(&mut self, at: usize, to: usize) {
+			let rel = to as i64 - (at as i64 + 4);
+			self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+		}
+
+		// A short jcc over the bail out for op, taken when things are fine.
+		// The bail out is mov eax, op; jmp epilogue, 10 bytes.
+		fn or_bail(&mut self, ok: u8, op: usize) {
+			self.emit(&[ok, 10, 0xB8]);
+			self.imm32(op as i64);
+			self.emit(&[0xE9]);
+			let at = self.rel32();
+			self.exits.push(at);
+		}
+
+		// bail if cost more steps would go over the limit
+		fn budget(&mut self, cost: u64, op: usize) {
+			if self.checked {
+				// lea rax, [r14 + cost]; cmp rax, r15; jbe
+				self.emit(&[0x49, 0x8D, 0x86]);
+				self.imm32(cost as i64);
+				self.emit(&[0x4C, 0x39, 0xF8]);
+				self.or_bail(JBE, op);
+			}
+		}
+
+		fn count(&mut self, cost: u64) {
+			if self.checked {
+				// add r14, cost
+				self.emit(&[0x49, 0x81, 0xC6]);
+				self.imm32(cost as i64);
+			}
+		}
+
+		fn op(&mut self, fast: &Fast, i: usize, out: bool) {
+			match fast.ops[i] {
+				FastOp::Add(n) => {
+					self.budget(n as u64, i);
+					if self.checked && !self.wrap {
+						// movzx eax, byte [rbx]; add eax, n; cmp eax, 255; jbe
+						self.emit(&[0x0F, 0xB6, 0x03, 0x05]);
+						self.imm32(n as i64);
+						self.emit(&[0x3D, 0xFF, 0, 0, 0]);
+						self.or_bail(JBE, i);
+						// mov [rbx], al
+						self.emit(&[0x88, 0x03]);
+					} else {
+						// add byte [rbx], n
+						self.emit(&[0x80, 0x03, n as u8]);
+					}
+					self.count(n as u64);
+				}
+				FastOp::Sub(n) => {
+					self.budget(n as u64, i);
+					if self.checked && !self.wrap {
+						// movzx eax, byte [rbx]; sub eax, n; jae
+						self.emit(&[0x0F, 0xB6, 0x03, 0x2D]);
+						self.imm32(n as i64);
+						self.or_bail(JAE, i);
+						self.emit(&[0x88, 0x03]);
+					} else {
+						// sub byte [rbx], n
+						self.emit(&[0x80, 0x2B, n as u8]);
+					}
+					self.count(n as u64);
+				}
+				FastOp::Right(n) => {
+					self.budget(n as u64, i);
+					if self.checked {
+						// lea rax, [rbx + n]; cmp rax, r13; jb; mov rbx, rax
+						self.emit(&[0x48, 0x8D, 0x83]);
+						self.imm32(n as i64);
+						self.emit(&[0x4C, 0x39, 0xE8]);
+						self.or_bail(JB, i);
+						self.emit(&[0x48, 0x89, 0xC3]);
+					} else {
+						// add rbx, n
+						self.emit(&[0x48, 0x81, 0xC3]);
+						self.imm32(n as i64);
+					}
+					self.count(n as u64);
+				}
+				FastOp::Left(n) => {
+					self.budget(n as u64, i);
+					if self.checked {
+						// mov rax, rbx; sub rax, n; cmp rax, r12; jae; mov rbx, rax
+						self.emit(&[0x48, 0x89, 0xD8, 0x48, 0x2D]);
+						self.imm32(n as i64);
+						self.emit(&[0x4C, 0x39, 0xE0]);
+						self.or_bail(JAE, i);
+						self.emit(&[0x48, 0x89, 0xC3]);
+					} else {
+						// sub rbx, n
+						self.emit(&[0x48, 0x81, 0xEB]);
+						self.imm32(n as i64);
+					}
+					self.count(n as u64);
+				}
+				FastOp::Open(close) => {
+					self.budget(1, i);
+					self.count(1);
+					// cmp byte [rbx], 0; je past the ]
+					self.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x84]);
+					let at = self.rel32();
+					self.jumps.push((at, close + 1));
+				}
+				FastOp::Close(open) => {
+					self.budget(1, i);
+					self.count(1);
+					// cmp byte [rbx], 0; jne past the [
+					self.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x85]);
+					let at = self.rel32();
+					self.jumps.push((at, open + 1));
+				}
+				FastOp::Io => {
+					self.budget(1, i);
+					self.count(1);
+					if out {
+						// movzx esi, byte [rbx]
+						self.emit(&[0x0F, 0xB6, 0x33]);
+						self.call(putch as *const () as usize);
+					} else {
+						self.call(getch as *const () as usize);
+						self.emit(&[0x88, 0x03]);
+					}
+				}
+				FastOp::Transfer(t) => self.transfer(fast, i, t),
+				FastOp::Scan(k) => self.scan(i, k),
+			}
+		}
+
+		fn call(&mut self, f: usize) {
+			// mov rdi, rbp; mov rax, f; call rax
+			self.emit(&[0x48, 0x89, 0xEF, 0x48, 0xB8]);
+			self.emit(&(f as u64).to_le_bytes());
+			self.emit(&[0xFF, 0xD0]);
+		}
+
+		fn transfer(&mut self, fast: &Fast, i: usize, t: usize) {
+			let t = &fast.transfers[t];
+
+			// movzx eax, byte [rbx]; test eax, eax; je zero
+			self.emit(&[0x0F, 0xB6, 0x03, 0x85, 0xC0, 0x0F, 0x84]);
+			let zero = self.rel32();
+
+			if self.checked {
+				// rcx = 1 + v * (body + 1), the steps this takes
+				// imul rcx, rax, body + 1; add rcx, 1
+				self.emit(&[0x48, 0x69, 0xC8]);
+				self.imm32(t.body as i64 + 1);
+				self.emit(&[0x48, 0x83, 0xC1, 0x01]);
+				// mov rdx, r14; add rdx, rcx; cmp rdx, r15; jbe
+				self.emit(&[0x4C, 0x89, 0xF2, 0x48, 0x01, 0xCA, 0x4C, 0x39, 0xFA]);
+				self.or_bail(JBE, i);
+
+				// lea rdx, [rbx + lo]; cmp rdx, r12; jae
+				self.emit(&[0x48, 0x8D, 0x93]);
+				self.imm32(t.lo as i64);
+				self.emit(&[0x4C, 0x39, 0xE2]);
+				self.or_bail(JAE, i);
+				// lea rdx, [rbx + hi]; cmp rdx, r13; jb
+				self.emit(&[0x48, 0x8D, 0x93]);
+				self.imm32(t.hi as i64);
+				self.emit(&[0x4C, 0x39, 0xEA]);
+				self.or_bail(JB, i);
+
+				if !self.wrap {
+					for &(o, k) in t.targets.iter() {
+						// movzx edx, byte [rbx + o]; imul rsi, rax, k;
+						// add rdx, rsi; cmp rdx, 255; jbe
+						self.emit(&[0x0F, 0xB6, 0x93]);
+						self.imm32(o as i64);
+						self.emit(&[0x48, 0x69, 0xF0]);
+						self.imm32(k);
+						self.emit(&[0x48, 0x01, 0xF2, 0x48, 0x81, 0xFA]);
+						self.imm32(255);
+						self.or_bail(JBE, i);
+					}
+				}
+			}
+
+			for &(o, k) in t.targets.iter() {
+				// imul esi, eax, k; add byte [rbx + o], sil
+				self.emit(&[0x69, 0xF0]);
+				self.imm32(k);
+				self.emit(&[0x40, 0x00, 0xB3]);
+				self.imm32(o as i64);
+			}
+			// mov byte [rbx], 0
+			self.emit(&[0xC6, 0x03, 0x00]);
+			if self.checked {
+				// add r14, rcx
+				self.emit(&[0x49, 0x01, 0xCE]);
+			}
+			self.emit(&[0xE9]);
+			let done = self.rel32();
+
+			let here = self.code.len();
+			self.patch(zero, here);
+			self.budget(1, i);
+			self.count(1);
+
+			let here = self.code.len();
+			self.patch(done, here);
+		}
+
+		fn scan(&mut self, i: usize, k: isize) {
+			if !self.checked {
+				// top: cmp byte [rbx], 0; je done; add rbx, k; jmp top
+				let top = self.code.len();
+				self.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x84]);
+				let done = self.rel32();
+				self.emit(&[0x48, 0x81, 0xC3]);
+				self.imm32(k as i64);
+				self.emit(&[0xE9]);
+				let back = self.rel32();
+				self.patch(back, top);
+				let here = self.code.len();
+				self.patch(done, here);
+				return;
+			}
+
+			// walk rdx along adding up steps in rcx, only moving rbx and
+			// r14 once we know it all went fine
+			// mov rdx, rbx; mov ecx, 1
+			self.emit(&[0x48, 0x89, 0xDA, 0xB9, 1, 0, 0, 0]);
+			let top = self.code.len();
+			// cmp byte [rdx], 0; je found
+			self.emit(&[0x80, 0x3A, 0x00, 0x0F, 0x84]);
+			let found = self.rel32();
+			// add rdx, k; cmp rdx, r12; jae; cmp rdx, r13; jb
+			self.emit(&[0x48, 0x81, 0xC2]);
+			self.imm32(k as i64);
+			self.emit(&[0x4C, 0x39, 0xE2]);
+			self.or_bail(JAE, i);
+			self.emit(&[0x4C, 0x39, 0xEA]);
+			self.or_bail(JB, i);
+			// add rcx, |k| + 1; jmp top
+			self.emit(&[0x48, 0x81, 0xC1]);
+			self.imm32(k.abs() as i64 + 1);
+			self.emit(&[0xE9]);
+			let back = self.rel32();
+			self.patch(back, top);
+
+			let here = self.code.len();
+			self.patch(found, here);
+			// mov rax, r14; add rax, rcx; cmp rax, r15; jbe
+			self.emit(&[0x4C, 0x89, 0xF0, 0x48, 0x01, 0xC8, 0x4C, 0x39, 0xF8]);
+			self.or_bail(JBE, i);
+			// mov r14, rax; mov rbx, rdx
+			self.emit(&[0x49, 0x89, 0xC6, 0x48, 0x89, 0xD3]);
+		}
+	}
+
+	const JB: u8 = 0x72;
+	const JAE: u8 = 0x73;
+	const JBE: u8 = 0x76;
+
+	// The code and the offset of each op in it.
+	fn assemble(machine: &Machine, fast: &Fast, checked: bool) -> (Vec<u8>, Vec<usize>) {
+		let mut asm = Asm {
+			code: vec![],
+			checked,
+			wrap: machine.config.wrap,
+			exits: vec![],
+			jumps: vec![],
+		};
+
+		// push rbp, rbx, r12-r15; sub rsp, 8 to keep calls aligned;
+		// mov rbp, rdi; load r12, r13, rbx, r14, r15 from the Ctx and jump to
+		// its entry
+		asm.emit(&[
+			0x55, 0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57, 0x48, 0x83,
+			0xEC, 0x08, 0x48, 0x89, 0xFD, 0x4C, 0x8B, 0x65, 0x00, 0x4C, 0x8B, 0x6D,
+			0x08, 0x48, 0x8B, 0x5D, 0x10, 0x4C, 0x8B, 0x75, 0x18, 0x4C, 0x8B, 0x7D,
+			0x20, 0x48, 0x8B, 0x45, 0x30, 0xFF, 0xE0,
+		]);
+
+		let mut starts = vec![];
+		for i in 0..fast.ops.len() {
+			starts.push(asm.code.len());
+			let out = machine.prog.code[fast.pcs[i]] == b'.';
+			asm.op(fast, i, out);
+		}
+		starts.push(asm.code.len());
+
+		// made it to the end: mov eax, ops
+		asm.emit(&[0xB8]);
+		asm.imm32(fast.ops.len() as i64);
+
+		// store rbx and r14 back, then undo the prologue
+		let epilogue = asm.code.len();
+		asm.emit(&[
+			0x48, 0x89, 0x5D, 0x10, 0x4C, 0x89, 0x75, 0x18, 0x48, 0x83, 0xC4, 0x08,
+			0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5B, 0x5D, 0xC3,
+		]);
+
+		for at in asm.exits.clone() {
+			asm.patch(at, epilogue);
+		}
+		for (at, op) in asm.jumps.clone() {
+			asm.patch(at, starts[op]);
+		}
+
+		(asm.code, starts)
+	}
+
+	// Run the machine from wherever it is to the end, jitted where possible.
+	pub fn run(machine: &mut Machine, checked: bool) -> Result<u64, Fault> {
+		let fast = Fast::new(&machine.prog);
+
+		// jitted code only starts at an op
+		let start = loop {
+			match fast.pcs.binary_search(&machine.pc) {
+				Ok(start) => break start,
+				Err(_) => machine.step()?,
+			}
+		};
+
+		let (code, starts) = assemble(machine, &fast, checked);
+		let text = Map::new(code.len(), PROT_READ | PROT_WRITE);
+		unsafe {
+			ptr::copy_nonoverlapping(code.as_ptr(), text.addr, code.len());
+		}
+		text.protect(0, text.len, PROT_READ | PROT_EXEC);
+
+		let tape_len = machine.config.tape;
+		let tape = Map::new(GUARD + tape_len + GUARD, PROT_READ | PROT_WRITE);
+		tape.protect(0, GUARD, PROT_NONE);
+		let after = (GUARD + tape_len).div_ceil(PAGE) * PAGE;
+		tape.protect(after, tape.len - after, PROT_NONE);
+
+		let base = unsafe { tape.addr.add(GUARD) };
+		for (i, c) in machine.mem.iter().enumerate() {
+			unsafe { *base.add(i) = *c as u8 };
+		}
+
+		let mut ctx = Ctx {
+			base,
+			end: unsafe { base.add(tape_len) },
+			cell: unsafe { base.add(machine.mp) },
+			steps: machine.steps,
+			limit: machine.config.steps.unwrap_or(u64::MAX),
+			machine: machine as *mut Machine,
+			entry: unsafe { text.addr.add(starts[start]) },
+		};
+
+		// the op it stopped before, every op when it ran to the end
+		let stopped = unsafe {
+			let code: extern "C" fn(*mut Ctx) -> u32 = std::mem::transmute(text.addr);
+			code(&mut ctx) as usize
+		};
+
+		// unchecked code can end up off the tape without touching a guard
+		// page. Keep what's on it, but there's no telling which op took it
+		// there
+		let off = ctx.cell as isize - base as isize;
+		let err = if off < 0 {
+			Some(Error::BeforeStart)
+		} else if off as usize >= tape_len {
+			Some(Error::PastEnd)
+		} else {
+			None
+		};
+		let mp = (off.max(0) as usize).min(tape_len - 1);
+
+		let tape = unsafe { std::slice::from_raw_parts(base, tape_len) };
+		let used = tape
+			.iter()
+			.rposition(|c| *c != 0)
+			.map_or(0, |c| c + 1)
+			.max(mp + 1)
+			.max(machine.mem.len());
+		machine.mem = tape[..used].iter().map(|c| *c as u32).collect();
+		machine.mp = mp;
+		machine.steps = ctx.steps;
+		machine.pc = fast.pcs[stopped];
+		if let Some(err) = err {
+			return Err(Fault {
+				err,
+				pc: machine.pc,
+				at: machine.prog.at.get(machine.pc).or(machine.prog.at.last()).cloned().unwrap_or(0),
+				mp,
+				steps: machine.steps,
+				tracked: checked,
+			});
+		}
+
+		// let the interpreter finish off whatever made it bail, it checks a
+		// clean exit and flushes output either way
+		machine.run()
+	}
+}
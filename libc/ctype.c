@@ -0,0 +1,98 @@
+#include <ctype.h>
+
+int isdigit(int c) {
+  if (c < '0') {
+    return 0;
+  }
+  if (c > '9') {
+    return 0;
+  }
+  return 1;
+}
+
+int isupper(int c) {
+  if (c < 'A') {
+    return 0;
+  }
+  if (c > 'Z') {
+    return 0;
+  }
+  return 1;
+}
+
+int islower(int c) {
+  if (c < 'a') {
+    return 0;
+  }
+  if (c > 'z') {
+    return 0;
+  }
+  return 1;
+}
+
+int isalpha(int c) {
+  if (isupper(c)) {
+    return 1;
+  }
+  return islower(c);
+}
+
+int isalnum(int c) {
+  if (isalpha(c)) {
+    return 1;
+  }
+  return isdigit(c);
+}
+
+int isxdigit(int c) {
+  if (isdigit(c)) {
+    return 1;
+  }
+  // folds lowercase onto uppercase
+  c = toupper(c);
+  if (c < 'A') {
+    return 0;
+  }
+  if (c > 'F') {
+    return 0;
+  }
+  return 1;
+}
+
+// space, \t, \n, \v, \f and \r
+int isspace(int c) {
+  if (c == ' ') {
+    return 1;
+  }
+  if (c < '\t') {
+    return 0;
+  }
+  if (c > '\r') {
+    return 0;
+  }
+  return 1;
+}
+
+int isprint(int c) {
+  if (c < ' ') {
+    return 0;
+  }
+  if (c > '~') {
+    return 0;
+  }
+  return 1;
+}
+
+int toupper(int c) {
+  if (islower(c)) {
+    return c - 'a' + 'A';
+  }
+  return c;
+}
+
+int tolower(int c) {
+  if (isupper(c)) {
+    return c - 'A' + 'a';
+  }
+  return c;
+}
@@ -0,0 +1,14 @@
+#pragma once
+
+// plain ascii, 1 for yes and 0 for no
+
+int isdigit(int c);
+int isxdigit(int c);
+int isalpha(int c);
+int isalnum(int c);
+int isupper(int c);
+int islower(int c);
+int isspace(int c);
+int isprint(int c);
+int toupper(int c);
+int tolower(int c);
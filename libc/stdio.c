@@ -0,0 +1,105 @@
+#include <stdarg.h>
+#include <stdio.h>
+
+void putstr(const char *s) {
+  while (*s) {
+    putchar(*s);
+    s++;
+  }
+}
+
+// biggest a cell gets is 3 digits
+void putdec(int n) {
+  int digs = 1;
+  while (n / digs >= 10) {
+    digs *= 10;
+  }
+
+  while (digs) {
+    putchar('0' + n / digs % 10);
+    digs /= 10;
+  }
+}
+
+int puts(const char *s) {
+  putstr(s);
+  putchar('\n');
+  return 0;
+}
+
+char *gets(char *s) {
+  char *p = s;
+  unsigned char c = getchar();
+  while (c) {
+    if (c == '\n') {
+      break;
+    }
+    *p = c;
+    p++;
+    c = getchar();
+  }
+  *p = 0;
+  return s;
+}
+
+static void puthex(int n) {
+  // itoa needs a buffer and we can't have arrays on the stack
+  int digs = 1;
+  while (n / digs >= 16) {
+    digs *= 16;
+  }
+
+  while (digs) {
+    int d = n / digs % 16;
+    if (d < 10) {
+      putchar('0' + d);
+    } else {
+      putchar('a' + d - 10);
+    }
+    digs /= 16;
+  }
+}
+
+int printf(const char *fmt, ...) {
+  va_list ap;
+  va_start(ap, fmt);
+
+  while (*fmt) {
+    if (*fmt != '%') {
+      putchar(*fmt);
+      fmt++;
+      continue;
+    }
+
+    fmt++;
+    char c = *fmt;
+    if (c == 'd') {
+      putdec(va_arg(ap, int));
+    }
+    if (c == 'u') {
+      putdec(va_arg(ap, unsigned));
+    }
+    if (c == 'x') {
+      puthex(va_arg(ap, int));
+    }
+    if (c == 'c') {
+      putchar(va_arg(ap, int));
+    }
+    if (c == 's') {
+      putstr(va_arg(ap, char *));
+    }
+    if (c == '%') {
+      putchar('%');
+    }
+    // a lone % on the end
+    if (!c) {
+      break;
+    }
+    fmt++;
+  }
+
+  va_end(ap);
+  // supposed to be how much got printed but that'd be another counter
+  // threaded through everything for something nobody looks at
+  return 0;
+}
@@ -0,0 +1,18 @@
+#pragma once
+
+// The runtime only has `.` and `,` so these two are built right into the
+// compiler, everything else here is made out of them. getchar gives back 0
+// at the end of input.
+void putchar(unsigned char c);
+unsigned char getchar(void);
+
+// no newline on the end, unlike puts
+void putstr(const char *s);
+void putdec(int n);
+int puts(const char *s);
+// reads a line, dropping the newline. There's no way to know how big s is
+// so make it big.
+char *gets(char *s);
+
+// %d %u %c %s %x and %%, no widths or anything fancy
+int printf(const char *fmt, ...);
@@ -0,0 +1,45 @@
+#include <ctype.h>
+#include <stdlib.h>
+
+// skips leading space and stops at the first thing that isn't a digit
+int atoi(const char *s) {
+  int n = 0;
+  while (isspace(*s)) {
+    s++;
+  }
+  while (isdigit(*s)) {
+    n = n * 10 + (*s - '0');
+    s++;
+  }
+  return n;
+}
+
+// digits come out backwards so flip them around after
+char *itoa(int n, char *buf, int base) {
+  char *p = buf;
+  do {
+    int d = n % base;
+    if (d < 10) {
+      *p = '0' + d;
+    } else {
+      *p = 'a' + d - 10;
+    }
+    p++;
+    n = n / base;
+  } while (n);
+  *p = 0;
+
+  char *lo = buf;
+  char *hi = p - 1;
+  while (lo < hi) {
+    char t = *lo;
+    *lo = *hi;
+    *hi = t;
+    lo++;
+    hi--;
+  }
+  return buf;
+}
+
+// nothing's ever negative
+int abs(int n) { return n; }
@@ -0,0 +1,9 @@
+#pragma once
+
+// no negatives here, a cell can't go below 0
+
+int atoi(const char *s);
+// writes n in base 2 through 16 into buf and returns buf, which needs room
+// for 9 chars to be safe
+char *itoa(int n, char *buf, int base);
+int abs(int n);
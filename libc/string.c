@@ -0,0 +1,149 @@
+#include <string.h>
+
+// Written for bfcc, not for speed: one thing per line, nested ifs instead of
+// && and || so clang doesn't hand us phis for every condition. No globals
+// either, the linker can't tell one module's string constants from another's.
+
+size_t strlen(const char *s) {
+  size_t n = 0;
+  while (*s) {
+    s++;
+    n++;
+  }
+  return n;
+}
+
+int strcmp(const char *a, const char *b) {
+  while (*a) {
+    if (*a != *b) {
+      break;
+    }
+    a++;
+    b++;
+  }
+
+  if (*a == *b) {
+    return 0;
+  }
+  if ((unsigned char)*a < (unsigned char)*b) {
+    return -1;
+  }
+  return 1;
+}
+
+int strncmp(const char *a, const char *b, size_t n) {
+  while (n) {
+    if (*a != *b) {
+      if ((unsigned char)*a < (unsigned char)*b) {
+        return -1;
+      }
+      return 1;
+    }
+    if (!*a) {
+      return 0;
+    }
+    a++;
+    b++;
+    n--;
+  }
+  return 0;
+}
+
+char *strcpy(char *dst, const char *src) {
+  char *d = dst;
+  while (*src) {
+    *d = *src;
+    d++;
+    src++;
+  }
+  *d = 0;
+  return dst;
+}
+
+// pads out with 0s like the real one, and also like the real one won't end
+// dst with a 0 if src is too long
+char *strncpy(char *dst, const char *src, size_t n) {
+  char *d = dst;
+  while (n) {
+    *d = *src;
+    if (*src) {
+      src++;
+    }
+    d++;
+    n--;
+  }
+  return dst;
+}
+
+char *strcat(char *dst, const char *src) {
+  strcpy(dst + strlen(dst), src);
+  return dst;
+}
+
+char *strchr(const char *s, int c) {
+  while (*s != (char)c) {
+    if (!*s) {
+      return 0;
+    }
+    s++;
+  }
+  return (char *)s;
+}
+
+void *memset(void *s, int c, size_t n) {
+  unsigned char *p = s;
+  while (n) {
+    *p = c;
+    p++;
+    n--;
+  }
+  return s;
+}
+
+void *memcpy(void *dst, const void *src, size_t n) {
+  unsigned char *d = dst;
+  const unsigned char *s = src;
+  while (n) {
+    *d = *s;
+    d++;
+    s++;
+    n--;
+  }
+  return dst;
+}
+
+// copies from the back when dst overlaps the end of src
+void *memmove(void *dst, const void *src, size_t n) {
+  unsigned char *d = dst;
+  const unsigned char *s = src;
+  if (d <= s) {
+    return memcpy(dst, src, n);
+  }
+
+  d += n;
+  s += n;
+  while (n) {
+    d--;
+    s--;
+    *d = *s;
+    n--;
+  }
+  return dst;
+}
+
+int memcmp(const void *a, const void *b, size_t n) {
+  const unsigned char *x = a;
+  const unsigned char *y = b;
+  while (n) {
+    if (*x != *y) {
+      if (*x < *y) {
+        return -1;
+      }
+      return 1;
+    }
+    x++;
+    y++;
+    n--;
+  }
+  return 0;
+}
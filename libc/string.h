@@ -0,0 +1,20 @@
+#pragma once
+
+#include <stddef.h>
+
+// Everything lives in cells so lengths top out at 255. strcmp and friends
+// give back 0, 1 for a sorting after b or 255 (-1) for before since that's
+// all an unsigned cell can say.
+
+size_t strlen(const char *s);
+int strcmp(const char *a, const char *b);
+int strncmp(const char *a, const char *b, size_t n);
+char *strcpy(char *dst, const char *src);
+char *strncpy(char *dst, const char *src, size_t n);
+char *strcat(char *dst, const char *src);
+char *strchr(const char *s, int c);
+
+void *memset(void *s, int c, size_t n);
+void *memcpy(void *dst, const void *src, size_t n);
+void *memmove(void *dst, const void *src, size_t n);
+int memcmp(const void *a, const void *b, size_t n);
@@ -1,28 +1,10 @@
 #pragma once
 
-#define uint8_t unsigned char
-
-void putchar(uint8_t c);
-uint8_t getchar(void);
-
-void putstr(char *s) {
-	while(s) {
-		putchar(s);
-		s++;
-	}
-}
+// everything from the bundled libc in one go, see libc/
 
-void putdec(int n) {
-  if (!n) {
-    putchar('0');
-    return;
-  }
-
-  int digs = 1;
-  while(n / digs > 10) digs *= 10;
+#define uint8_t unsigned char
 
-  while(digs) {
-    putchar('0' + ((n / digs)%10));
-    digs /= 10;
-  }
-}
+#include <ctype.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
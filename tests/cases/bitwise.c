@@ -1,6 +1,6 @@
 #include "stdfuck.h"
 
-// TEST:{ "name": "bitwise", "output": "AAA,BAB,CAC,DAD,EAE,BAB,BBA,DAD,DBC,FAF,CAC,DAD,CCA,DCB,GAG,DAD,DBC,DCB,DDA,HAH,EAE,FAF,GAG,HAH,EEA,"}
+// TEST:{ "name": "bitwise", "output": "0/0/0,1/0/1,2/0/2,3/0/3,4/0/4,1/0/1,1/1/0,3/0/3,3/1/2,5/0/5,2/0/2,3/0/3,2/2/0,3/2/1,6/0/6,3/0/3,3/1/2,3/2/1,3/3/0,7/0/7,4/0/4,5/0/5,6/0/6,7/0/7,4/4/0,"}
 void main(void) {
   for (unsigned int i = 0; i < 5; i++) {
     for (unsigned int j = 0; j < 5; j++) {
@@ -0,0 +1,13 @@
+#include "stdfuck.h"
+
+void shout(uint8_t c) {
+  putchar(c);
+  putchar('!');
+}
+
+// nothing here can break the promise and --check should be able to tell
+// TEST:{ "name": "check clean", "check": [], "output": "a!b!" }
+void main(void) {
+  shout('a');
+  shout('b');
+};
@@ -0,0 +1,11 @@
+#include "stdfuck.h"
+
+// the runs keep a in a cell but --check has to assume getchar can give back
+// anything, and twice most of that doesn't fit. adds wrap so it has to be the
+// doubling that goes over
+// TEST:{ "name": "check overflow", "check": ["could go past 255"], "runs": [{ "input": "", "output": "A" }, { "input": "\u0001", "output": "C" }] }
+void main(void) {
+  uint8_t a = 'A';
+  a += getchar() * 2;
+  putchar(a);
+};
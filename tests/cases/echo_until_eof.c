@@ -1,7 +1,6 @@
 #include "stdfuck.h"
 
-// TODO we need to support test input for this stuff to work
-// TEST:{ "skip": true, "name": "echo until eof", "output": "hey!", "input": "hey!" }
+// TEST:{ "name": "echo until eof", "runs": [{ "input": "hey!", "output": "hey!" }, { "input": "", "output": "" }, { "input": "a\u0000b", "output": "a" }] }
 int main() {
   uint8_t a;
 
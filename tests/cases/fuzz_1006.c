@@ -0,0 +1,15 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 1006 at -O1: bfcc panicked: idk how to do multiple phis
+// TEST:{ "name": "fuzz 1006", "output": "\u0002" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 186;
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    for (uint8_t i1 = 0; i1 < 5; i1++) {
+      v0 = (uint8_t)(v1 >> (v0 & 7));
+    }
+  }
+  putchar(v0);
+  return 0;
+}
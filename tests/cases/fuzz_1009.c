@@ -0,0 +1,32 @@
+#include "stdfuck.h"
+
+uint8_t f0(uint8_t a, uint8_t b) {
+  return 0;
+}
+
+// found by verify --fuzz --seed 1009 at -O0: cell overflow at instruction 42943 (byte 64913), cell 256, after 18344 steps
+// TEST:{ "name": "fuzz 1009", "output": "\u0001\u0000\u0000\u0000" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 0;
+  uint8_t v2 = 0;
+  uint8_t v3 = 0;
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    v2 = (uint8_t)(v1 & (uint8_t)(0 % (v0 | 1)));
+    v1 = (uint8_t)(v1 / (f0(v2, (uint8_t)(v0 ^ 0)) | 1));
+    v2 = (uint8_t)(v2 & ((uint8_t)(0 % (v1 | 1)) != (v2 != v1)));
+    v2 = ((uint8_t)((v0 >= 0) % (v0 | 1)) == ((uint8_t)(v0 | 0) == (uint8_t)(v3 << (v3 & 7))));
+  }
+  putchar((0 == (0 != v1)));
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    v2 = f0(((v3 > v2) <= 0), (uint8_t)(f0(v2, v1) & (uint8_t)(0 >> (v1 & 7))));
+    v2 = (uint8_t)((uint8_t)((0 < v3) * f0(v2, v1)) ^ v3);
+    v0 = v0;
+  }
+  v2 = f0((uint8_t)((uint8_t)(v2 >> (v3 & 7)) / ((v0 != v0) | 1)), (v1 != (uint8_t)(0 >> (v3 & 7))));
+  putchar(((uint8_t)(v2 | v0) != 0));
+  v0 = f0(f0(f0(v1, 0), f0(v3, 0)), (uint8_t)(f0(v1, v3) * v2));
+  putchar((uint8_t)((uint8_t)(v2 - v2) / ((v0 > 0) | 1)));
+  putchar(v3);
+  return 0;
+}
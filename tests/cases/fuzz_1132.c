@@ -0,0 +1,42 @@
+#include "stdfuck.h"
+
+uint8_t f0(uint8_t a, uint8_t b) {
+  return (uint8_t)((uint8_t)((a != b) ^ (36 != 106)) + (a > 226));
+}
+
+uint8_t f1(uint8_t a, uint8_t b) {
+  return ((uint8_t)((uint8_t)(95 & a) >> ((uint8_t)(b * b) & 7)) <= (uint8_t)((uint8_t)(b * 127) % ((uint8_t)(b ^ a) | 1)));
+}
+
+// found by verify --fuzz --seed 1132 at -O0: cell overflow at instruction 61406 (byte 108167), cell 256, after 1896901 steps
+// at -O1 the loop exit printed a phi the back edge had already overwritten
+// TEST:{ "name": "fuzz 1132", "output": "\u0000\u0001\u0000å" }
+int main() {
+  uint8_t v0 = 24;
+  uint8_t v1 = 229;
+  uint8_t v2 = 69;
+  if ((uint8_t)(0 >> ((uint8_t)(v1 ^ 0) & 7))) {
+    v0 = (v1 != (uint8_t)(0 ^ (uint8_t)(v1 | 0)));
+  }
+  v0 = (f0(0, v1) <= f1((uint8_t)(v1 - v2), (v2 > v1)));
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    for (uint8_t i1 = 0; i1 < 1; i1++) {
+      v2 = v1;
+      v1 = f0(((v2 <= 0) >= (uint8_t)(v0 | v0)), (0 == (v1 < v0)));
+    }
+    v1 = f0(v2, (uint8_t)(0 >> ((v0 > v0) & 7)));
+    if (v0) {
+      v1 = ((v2 >= (uint8_t)(0 & v2)) != f0((v1 >= v0), f0(v2, v1)));
+      v1 = (uint8_t)((v0 == (uint8_t)(0 & v0)) & v1);
+      v1 = f0((f0(115, v1) <= (uint8_t)(v1 >> (108 & 7))), (uint8_t)((uint8_t)(v1 ^ 0) * (v0 < v1)));
+      putchar(v1);
+    } else {
+      v1 = v2;
+    }
+  }
+  v0 = f0((uint8_t)((uint8_t)(v0 % (v1 | 1)) >> ((v2 > v0) & 7)), (uint8_t)((uint8_t)(v0 ^ v0) | 0));
+  putchar(v0);
+  putchar(v1);
+  putchar(v2);
+  return 0;
+}
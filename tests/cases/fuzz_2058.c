@@ -0,0 +1,17 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 2058 at -O1: cell underflow at instruction 369 (byte 2211), cell 16, after 129 steps
+// TEST:{ "name": "fuzz 2058", "output": "x" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 0;
+  uint8_t v2 = 0;
+  uint8_t v3 = 130;
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    for (uint8_t i1 = 0; i1 < 4; i1++) {
+      v2 = (uint8_t)(184 ^ (uint8_t)(v3 + v2));
+    }
+  }
+  putchar(v2);
+  return 0;
+}
@@ -0,0 +1,25 @@
+#include "stdfuck.h"
+
+uint8_t f0(uint8_t a, uint8_t b) {
+  return 0;
+}
+
+uint8_t f1(uint8_t a, uint8_t b) {
+  return (uint8_t)(a % (220 | 1));
+}
+
+// found by verify --fuzz --seed 2067 at -O1: cell overflow at instruction 1019 (byte 4011), cell 16, after 17313 steps
+// TEST:{ "name": "fuzz 2067", "output": "\u0010\u001d\u001d\u001d" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 0;
+  uint8_t v2 = 51;
+  uint8_t v3 = 0;
+  uint8_t v4 = 0;
+  for (uint8_t i0 = 0; i0 < 4; i0++) {
+    v4 = (uint8_t)((uint8_t)(186 + v2) >> (v0 & 7));
+    putchar(f1(v4, 0));
+    v0 = v2;
+  }
+  return 0;
+}
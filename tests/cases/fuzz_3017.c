@@ -0,0 +1,16 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 3017 at -O1: cell overflow at instruction 2748 (byte 11219), cell 16, after 55127 steps
+// TEST:{ "name": "fuzz 3017", "output": "2" }
+int main() {
+  uint8_t v0 = 20;
+  for (uint8_t i0 = 0; i0 < 5; i0++) {
+    for (uint8_t i1 = 0; i1 < 1; i1++) {
+      v0 = (uint8_t)(v0 / (v0 | 1));
+    }
+  }
+  v0 = (uint8_t)(v0 ^ 199);
+  v0 = (uint8_t)(v0 - 149);
+  putchar(v0);
+  return 0;
+}
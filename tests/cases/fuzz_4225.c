@@ -0,0 +1,27 @@
+#include "stdfuck.h"
+
+uint8_t f0(uint8_t a, uint8_t b) {
+  return a;
+}
+
+// found by verify --fuzz --seed 4225 at -O1: cell overflow at instruction 2183 (byte 8922), cell 13, after 78156 steps
+// TEST:{ "name": "fuzz 4225", "output": "\u0001" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 0;
+  uint8_t v2 = 83;
+  uint8_t v3 = 0;
+  uint8_t v4 = 0;
+  v4 = (0 >= f0(95, 0));
+  for (uint8_t i0 = 0; i0 < 4; i0++) {
+    v3 = v4;
+    v1 = (v3 < 0);
+    if (v4) {
+      v4 = 64;
+    } else {
+      v2 = (uint8_t)((uint8_t)((uint8_t)(152 ^ v2) / (v3 | 1)) - (uint8_t)(153 ^ v2));
+    }
+  }
+  putchar(v2);
+  return 0;
+}
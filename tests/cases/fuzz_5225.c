@@ -0,0 +1,19 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 5225 at -O1: expected "ÿ", got "\u{1}"
+// TEST:{ "name": "fuzz 5225", "output": "ÿ" }
+int main() {
+  uint8_t v0 = 113;
+  uint8_t v1 = 179;
+  uint8_t v2 = 0;
+  uint8_t v3 = 0;
+  for (uint8_t i0 = 0; i0 < 1; i0++) {
+    for (uint8_t i1 = 0; i1 < 4; i1++) {
+      v1 = (v1 > v0);
+      v2 = v1;
+    }
+  }
+  v3 = (uint8_t)(v2 + 255);
+  putchar(v3);
+  return 0;
+}
@@ -0,0 +1,15 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 5247 at -O1: expected "\0\0\u{1}\u{1}", got "\0\0\0\0"
+// TEST:{ "name": "fuzz 5247", "output": "\u0000\u0000\u0001\u0001" }
+int main() {
+  uint8_t v0 = 88;
+  for (uint8_t i0 = 0; i0 < 3; i0++) {
+    if (v0) {
+      putchar(0);
+    }
+    putchar((0 >= v0));
+    v0 = 0;
+  }
+  return 0;
+}
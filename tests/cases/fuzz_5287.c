@@ -0,0 +1,17 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 5287 at -O1: cell underflow at instruction 585 (byte 3152), cell 22, after 623978 steps
+// TEST:{ "name": "fuzz 5287", "output": "·" }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 0;
+  for (uint8_t i0 = 0; i0 < 2; i0++) {
+    if ((uint8_t)(v0 + v1)) {
+      v0 = 15;
+    } else {
+      v1 = (uint8_t)(183 / (v1 | 1));
+    }
+  }
+  putchar(v1);
+  return 0;
+}
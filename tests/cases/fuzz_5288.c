@@ -0,0 +1,20 @@
+#include "stdfuck.h"
+
+// found by verify --fuzz --seed 5288 at -O1: expected "\u{1}\0\0", got "\0\0\0"
+// TEST:{ "name": "fuzz 5288", "output": "\u0001\u0000\u0000" }
+int main() {
+  uint8_t v0 = 252;
+  uint8_t v1 = 0;
+  uint8_t v2 = 0;
+  uint8_t v3 = 0;
+  for (uint8_t i0 = 0; i0 < 4; i0++) {
+    v1 = v2;
+    if (v2) {
+      putchar(v3);
+    } else {
+      v2 = v0;
+    }
+    v3 = (v1 <= 0);
+  }
+  return 0;
+}
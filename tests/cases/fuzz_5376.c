@@ -0,0 +1,30 @@
+#include "stdfuck.h"
+
+uint8_t f0(uint8_t a, uint8_t b) {
+  return (0 < (uint8_t)(b ^ 117));
+}
+
+uint8_t f1(uint8_t a, uint8_t b) {
+  return (uint8_t)(47 + b);
+}
+
+// found by verify --fuzz --seed 5376 at -O1: expected ".", got "0"
+// TEST:{ "name": "fuzz 5376", "output": "." }
+int main() {
+  uint8_t v0 = 0;
+  uint8_t v1 = 80;
+  uint8_t v2 = 0;
+  v2 = f0(0, 0);
+  v0 = 221;
+  v1 = (uint8_t)(v1 ^ v0);
+  for (uint8_t i0 = 0; i0 < 2; i0++) {
+    if (v2) {
+      v0 = (uint8_t)((0 < v1) >> ((v0 <= 161) & 7));
+    } else {
+      v2 = f0(0, v0);
+    }
+  }
+  v0 = (uint8_t)(f1(0, v0) - (15 <= (uint8_t)(v1 << (v0 & 7))));
+  putchar(v0);
+  return 0;
+}
@@ -0,0 +1,8 @@
+#include "stdfuck.h"
+
+// the first read's value never gets used, it still has to eat a byte
+// TEST:{ "name": "getchar skip", "runs": [{ "input": "xyz", "output": "y" }, { "input": "x", "output": "\u0000" }] }
+void main(void) {
+  getchar();
+  putchar(getchar());
+};
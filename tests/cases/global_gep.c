@@ -0,0 +1,16 @@
+#include "stdfuck.h"
+
+char grid[2][3];
+
+// a constant gep into a global takes every index into account, not just the
+// last one
+// TEST:{ "name": "global gep", "output": "GgG" }
+void main(void) {
+  grid[1][2] = 'G';
+  grid[0][1] = 'g';
+  putchar(grid[1][2]);
+  putchar(grid[0][1]);
+
+  char *row = grid[1];
+  putchar(row[2]);
+};
@@ -0,0 +1,19 @@
+#include "stdfuck.h"
+
+uint8_t buf[3];
+uint8_t n = 'G';
+
+void set(uint8_t *p, uint8_t v) { *p = v; }
+
+// set gets pointers into main's frame and into the globals and can't tell
+// them apart, they all count from the first cell
+// TEST:{ "name": "global pointers", "output": "LGn" }
+void main(void) {
+  uint8_t local = 0;
+  set(&local, 'L');
+  set(&buf[1], n);
+  set(&n, 'n');
+  putchar(local);
+  putchar(buf[1]);
+  putchar(n);
+};
@@ -0,0 +1,21 @@
+#include <ctype.h>
+#include <stdio.h>
+
+// TEST:{ "name": "libc ctype", "output": "1010 1011 Aa9 1" }
+int main() {
+  putdec(isdigit('5'));
+  putdec(isdigit('x'));
+  putdec(isalpha('q'));
+  putdec(isalpha('!'));
+  putchar(' ');
+  putdec(isspace('\n'));
+  putdec(isupper('a'));
+  putdec(islower('z'));
+  putdec(isxdigit('F'));
+  putchar(' ');
+  putchar(toupper('a'));
+  putchar(tolower('A'));
+  putchar(toupper('9'));
+  putchar(' ');
+  putdec(isalnum('7'));
+};
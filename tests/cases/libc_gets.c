@@ -0,0 +1,12 @@
+#include <stdio.h>
+#include <string.h>
+
+char line[16];
+
+// TEST:{ "name": "libc gets", "runs": [{ "input": "hello\nworld", "output": "5 hello" }, { "input": "hi", "output": "2 hi" }, { "output": "0 " }] }
+int main() {
+  gets(line);
+  putdec(strlen(line));
+  putchar(' ');
+  putstr(line);
+};
@@ -0,0 +1,8 @@
+#include <stdio.h>
+
+// built for avr since x86_64 expands va_arg into register save area stuff,
+// see varargs.c
+// TEST:{ "name": "libc printf", "triple": "avr-unknown-unknown", "output": "7 is 7, ff 100% a-ok" }
+int main() {
+  printf("%d is %u, %x %d%% %c-%s", 7, 7, 255, 100, 'a', "ok");
+};
@@ -0,0 +1,7 @@
+#include <stdio.h>
+
+// TEST:{ "name": "libc puts", "output": "hi\nthere\n" }
+int main() {
+  puts("hi");
+  puts("there");
+};
@@ -0,0 +1,16 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+char buf[9];
+
+// TEST:{ "name": "libc atoi itoa", "output": "142 ff 101 0" }
+int main() {
+  putdec(atoi("  142x"));
+  putchar(' ');
+
+  putstr(itoa(255, buf, 16));
+  putchar(' ');
+  putstr(itoa(5, buf, 2));
+  putchar(' ');
+  putstr(itoa(0, buf, 10));
+};
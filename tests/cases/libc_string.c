@@ -0,0 +1,27 @@
+#include <stdio.h>
+#include <string.h>
+
+char buf[4];
+
+// TEST:{ "name": "libc string", "output": "3 0 1 1 ok lo hey" }
+int main() {
+  putdec(strlen("abc"));
+  putchar(' ');
+  putdec(strcmp("ab", "ab"));
+  putchar(' ');
+  putdec(strcmp("b", "a"));
+  putchar(' ');
+  putdec(strncmp("abx", "aby", 2) == 0);
+  putchar(' ');
+
+  char *s = "ok";
+  if (strchr(s, 'k') == s + 1) {
+    putstr(s);
+  }
+  putchar(' ');
+  putstr(strchr("hello", 'l') + 1);
+  putchar(' ');
+
+  strcpy(buf, "hey");
+  putstr(buf);
+};
@@ -0,0 +1,15 @@
+#include <stdio.h>
+
+// libc has a static puthex of its own, neither should get in the other's way
+// TEST:{ "name": "link statics", "output": "<7>\nok\n" }
+static void puthex(int n) {
+  putchar('<');
+  putdec(n);
+  putchar('>');
+}
+
+int main() {
+  puthex(7);
+  puts("");
+  puts("ok");
+};
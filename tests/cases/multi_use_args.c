@@ -0,0 +1,12 @@
+#include "stdfuck.h"
+
+// kept out of main or -O1 folds c away
+__attribute__((noinline)) void show(uint8_t c) {
+  putchar(c);
+  putchar(c + 1);
+  putchar(c);
+}
+
+// -O0 stores args away first thing, so it's -O1 that reads one more than once
+// TEST:{ "name": "multi use args", "output": "ABA" }
+void main(void) { show('A'); };
@@ -0,0 +1,14 @@
+#include "stdfuck.h"
+
+// TEST:{ "name": "phi swap", "output": "21212" }
+int main() {
+  uint8_t a = 1;
+  uint8_t b = 2;
+  for (uint8_t i = 0; i < 5; i++) {
+    uint8_t t = a;
+    a = b;
+    b = t;
+    putchar('0' + a);
+  }
+  return 0;
+}
@@ -0,0 +1,18 @@
+#include "stdfuck.h"
+
+uint8_t twice(uint8_t a) { return a + a; }
+
+uint8_t zero(void) { return 0; }
+
+uint8_t seven(void) { return 7; }
+
+uint8_t add3(uint8_t a, uint8_t b, uint8_t c) { return a + b + c; }
+
+// the first seven has to hang around in a register while the other two calls
+// come back
+// TEST:{ "name": "return values", "output": "B0K" }
+void main(void) {
+  putchar(twice('!'));
+  putchar('0' + zero());
+  putchar(add3(seven(), twice(seven()), '6'));
+};
@@ -0,0 +1,20 @@
+#include "stdfuck.h"
+#include <stdarg.h>
+
+void putn(int n, ...) {
+  va_list ap;
+  va_start(ap, n);
+  while (n) {
+    putchar(va_arg(ap, int));
+    n -= 1;
+  }
+  va_end(ap);
+}
+
+// built for avr, where va_list is a plain pointer and clang leaves va_arg in
+// the ir, on x86_64 it turns into reads from a register save area
+// TEST:{ "name": "varargs", "triple": "avr-unknown-unknown", "output": "Hi!\n" }
+void main(void) {
+  putn(3, 'H', 'i', '!');
+  putn(1, '\n');
+};
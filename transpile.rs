@@ -0,0 +1,379 @@
+// Turns a brainfuck program into C or Rust source to build a native binary
+// from. It's fused the same way the interpreter fuses it (interp::Fast) so
+// runs become a single add or seek and clearing, moving and scanning loops
+// stop being loops.
+//
+// Checked code stops like bfexec would when a cell leaves its range (unless
+// wrapping) or the pointer leaves the tape, with the same exit status. A
+// whole fused op gets checked before it runs so the byte it blames is where
+// that op starts, not necessarily the exact instruction. Unchecked code wraps
+// cells and trusts the pointer to stay on the tape. Neither counts steps.
+
+use std::mem;
+
+use interp::{Config, Error, Fast, FastOp, Program};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+	C,
+	Rust,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flavor {
+	pub lang: Lang,
+	pub checked: bool,
+}
+
+impl Flavor {
+	pub fn from_name(name: &str) -> Option<Flavor> {
+		let (lang, checked) = match name {
+			"c" => (Lang::C, true),
+			"c-unchecked" => (Lang::C, false),
+			"rust" => (Lang::Rust, true),
+			"rust-unchecked" => (Lang::Rust, false),
+			_ => return None,
+		};
+		Some(Flavor {
+			lang,
+			checked,
+		})
+	}
+}
+
+struct Out<'a> {
+	lang: Lang,
+	checked: bool,
+	config: &'a Config,
+	max: u64,
+	src: String,
+	depth: usize,
+	faults: bool,
+}
+
+pub fn transpile(prog: &Program, config: &Config, flavor: Flavor) -> String {
+	let fast = Fast::new(prog);
+	let mut out = Out {
+		lang: flavor.lang,
+		checked: flavor.checked,
+		config,
+		max: (1u64 << config.cell_bits) - 1,
+		src: String::new(),
+		depth: 0,
+		faults: false,
+	};
+
+	out.depth = 1;
+	for (i, op) in fast.ops.iter().enumerate() {
+		out.op(&fast, *op, prog.at[fast.pcs[i]], prog.code[fast.pcs[i]]);
+	}
+
+	// the prelude only needs fault() if something turned out to call it
+	let body = mem::take(&mut out.src);
+	out.depth = 0;
+	out.prelude();
+	out.src.push_str(&body);
+	out.postlude();
+
+	out.src
+}
+
+impl<'a> Out<'a> {
+	fn line(&mut self, s: &str) {
+		let indent = match self.lang {
+			Lang::C => "\t",
+			Lang::Rust => "    ",
+		};
+		for _ in 0..self.depth {
+			self.src.push_str(indent);
+		}
+		self.src.push_str(s);
+		self.src.push('\n');
+	}
+
+	fn cell_type(&self) -> String {
+		match self.lang {
+			Lang::C => format!("uint{}_t", self.config.cell_bits),
+			Lang::Rust => format!("u{}", self.config.cell_bits),
+		}
+	}
+
+	fn prelude(&mut self) {
+		let header = format!(
+			"translated from brainfuck: {} cells of {} bits, {}",
+			self.config.tape,
+			self.config.cell_bits,
+			match (self.checked, self.config.wrap) {
+				(false, _) => "unchecked",
+				(true, false) => "checked",
+				(true, true) => "checked, cells wrap",
+			}
+		);
+		let t = self.cell_type();
+
+		match self.lang {
+			Lang::C => {
+				self.line(&format!("// {}", header));
+				self.line("#include <stdint.h>");
+				self.line("#include <stdio.h>");
+				self.line("#include <stdlib.h>");
+				self.line("");
+				self.line(&format!("#define TAPE {}", self.config.tape));
+				self.line("");
+				self.line(&format!("static {} m[TAPE];", t));
+				self.line("");
+				if self.faults {
+					self.line("static void fault(int status, const char *what, long at) {");
+					self.line("\tfflush(stdout);");
+					self.line("\tfprintf(stderr, \"%s at byte %ld\\n\", what, at);");
+					self.line("\texit(status);");
+					self.line("}");
+					self.line("");
+				}
+				self.line("int main(void) {");
+				self.line("\tsize_t p = 0;");
+			}
+			Lang::Rust => {
+				self.line(&format!("// {}", header));
+				self.line("#![allow(unused)]");
+				self.line("");
+				self.line("use std::io::{self, Read, Write};");
+				self.line("use std::process;");
+				self.line("");
+				self.line(&format!("const TAPE: usize = {};", self.config.tape));
+				self.line("");
+				if self.faults {
+					self.line("fn fault<W: Write>(out: &mut W, status: i32, what: &str, at: usize) -> ! {");
+					self.line("    out.flush().ok();");
+					self.line("    eprintln!(\"{} at byte {}\", what, at);");
+					self.line("    process::exit(status);");
+					self.line("}");
+					self.line("");
+				}
+				self.line("fn main() {");
+				self.line(&format!("    let mut m = vec![0{}; TAPE];", t));
+				self.line("    let mut p: usize = 0;");
+				self.line("    let stdin = io::stdin();");
+				self.line("    let mut input = stdin.lock().bytes();");
+				self.line("    let stdout = io::stdout();");
+				self.line("    let mut out = io::BufWriter::new(stdout.lock());");
+			}
+		}
+	}
+
+	fn postlude(&mut self) {
+		match self.lang {
+			Lang::C => {
+				self.line("\treturn 0;");
+				self.line("}");
+			}
+			Lang::Rust => {
+				self.line("    out.flush().ok();");
+				self.line("}");
+			}
+		}
+	}
+
+	// m[p], m[p + 2], m[p - 1]
+	fn cell(&self, o: isize) -> String {
+		if o == 0 {
+			"m[p]".to_string()
+		} else if o > 0 {
+			format!("m[p + {}]", o)
+		} else {
+			format!("m[p - {}]", -o)
+		}
+	}
+
+	// stop with err if cond holds. None is a condition that always does.
+	fn check(&mut self, cond: Option<String>, err: Error, at: usize) {
+		let cond = match (self.lang, cond) {
+			(_, Some(c)) => c,
+			(Lang::C, None) => "1".to_string(),
+			(Lang::Rust, None) => "true".to_string(),
+		};
+		self.faults = true;
+		let what = err.to_string();
+		let status = err.status();
+		match self.lang {
+			Lang::C => self.line(&format!(
+				"if ({}) fault({}, \"{}\", {});",
+				cond, status, what, at
+			)),
+			Lang::Rust => self.line(&format!(
+				"if {} {{ fault(&mut out, {}, \"{}\", {}); }}",
+				cond, status, what, at
+			)),
+		}
+	}
+
+	fn check_cells(&self) -> bool {
+		self.checked && !self.config.wrap
+	}
+
+	// checks for moving the pointer n cells either way
+	fn check_seek(&mut self, n: isize, at: usize) {
+		if !self.checked {
+			return;
+		}
+		let tape = self.config.tape as isize;
+		if n > 0 {
+			let cond = if n < tape {
+				Some(format!("p >= TAPE - {}", n))
+			} else {
+				None
+			};
+			self.check(cond, Error::PastEnd, at);
+		} else if n < 0 {
+			self.check(Some(format!("p < {}", -n)), Error::BeforeStart, at);
+		}
+	}
+
+	fn op(&mut self, fast: &Fast, op: FastOp, at: usize, c: u8) {
+		let modulus = self.max + 1;
+		let lang = self.lang;
+
+		match op {
+			FastOp::Add(n) | FastOp::Sub(n) => {
+				let add = matches!(op, FastOp::Add(_));
+				let n = n as u64;
+				if self.check_cells() {
+					if add {
+						let cond = if n <= self.max {
+							Some(format!("m[p] > {}", self.max - n))
+						} else {
+							None
+						};
+						self.check(cond, Error::Overflow, at);
+					} else {
+						let cond = if n <= self.max {
+							Some(format!("m[p] < {}", n))
+						} else {
+							None
+						};
+						self.check(cond, Error::Underflow, at);
+					}
+				}
+
+				let n = n % modulus;
+				if n == 0 {
+					return;
+				}
+				let line = match (lang, add) {
+					(Lang::C, true) => format!("m[p] += {};", n),
+					(Lang::C, false) => format!("m[p] -= {};", n),
+					(Lang::Rust, true) => format!("m[p] = m[p].wrapping_add({});", n),
+					(Lang::Rust, false) => format!("m[p] = m[p].wrapping_sub({});", n),
+				};
+				self.line(&line);
+			}
+			FastOp::Right(n) => {
+				self.check_seek(n as isize, at);
+				self.line(&format!("p += {};", n));
+			}
+			FastOp::Left(n) => {
+				self.check_seek(-(n as isize), at);
+				self.line(&format!("p -= {};", n));
+			}
+			FastOp::Open(_) => {
+				self.line(match lang {
+					Lang::C => "while (m[p]) {",
+					Lang::Rust => "while m[p] != 0 {",
+				});
+				self.depth += 1;
+			}
+			FastOp::Close(_) => {
+				self.depth -= 1;
+				self.line("}");
+			}
+			FastOp::Io => {
+				let line = match (lang, c) {
+					(Lang::C, b'.') => "putchar(m[p]);".to_string(),
+					(Lang::C, _) => "{ int c = getchar(); m[p] = c == EOF ? 0 : c; }".to_string(),
+					(Lang::Rust, b'.') => "out.write_all(&[m[p] as u8]).ok();".to_string(),
+					(Lang::Rust, _) => format!(
+						"m[p] = input.next().and_then(|b| b.ok()).unwrap_or(0) as {};",
+						self.cell_type()
+					),
+				};
+				self.line(&line);
+			}
+			FastOp::Transfer(t) => {
+				let t = &fast.transfers[t];
+
+				// [-] and friends
+				if t.targets.is_empty() {
+					self.line("m[p] = 0;");
+					return;
+				}
+
+				self.line(match lang {
+					Lang::C => "if (m[p]) {",
+					Lang::Rust => "if m[p] != 0 {",
+				});
+				self.depth += 1;
+				self.line(match lang {
+					Lang::C => "uint64_t v = m[p];",
+					Lang::Rust => "let v = m[p] as u64;",
+				});
+
+				// every check before any change so a fault leaves the tape
+				// like it was when the loop started
+				self.check_seek(t.hi, at);
+				self.check_seek(t.lo, at);
+				if self.check_cells() {
+					for &(o, k) in t.targets.iter() {
+						let cell = match lang {
+							Lang::C => format!("(uint64_t){}", self.cell(o)),
+							Lang::Rust => format!("({} as u64)", self.cell(o)),
+						};
+						if k > 0 {
+							let cond = format!("{} + v * {} > {}", cell, k, self.max);
+							self.check(Some(cond), Error::Overflow, at);
+						} else {
+							let cond = format!("{} < v * {}", cell, -k);
+							self.check(Some(cond), Error::Underflow, at);
+						}
+					}
+				}
+
+				let ty = self.cell_type();
+				for &(o, k) in t.targets.iter() {
+					let cell = self.cell(o);
+					let line = match (lang, k > 0) {
+						(Lang::C, true) => format!("{} = ({})({} + v * {});", cell, ty, cell, k),
+						(Lang::C, false) => format!("{} = ({})({} - v * {});", cell, ty, cell, -k),
+						(Lang::Rust, true) => format!(
+							"{} = ({} as u64).wrapping_add(v * {}) as {};",
+							cell, cell, k, ty
+						),
+						(Lang::Rust, false) => format!(
+							"{} = ({} as u64).wrapping_sub(v * {}) as {};",
+							cell, cell, -k, ty
+						),
+					};
+					self.line(&line);
+				}
+				self.line("m[p] = 0;");
+
+				self.depth -= 1;
+				self.line("}");
+			}
+			FastOp::Scan(k) => {
+				self.line(match lang {
+					Lang::C => "while (m[p]) {",
+					Lang::Rust => "while m[p] != 0 {",
+				});
+				self.depth += 1;
+				self.check_seek(k, at);
+				if k > 0 {
+					self.line(&format!("p += {};", k));
+				} else {
+					self.line(&format!("p -= {};", -k));
+				}
+				self.depth -= 1;
+				self.line("}");
+			}
+		}
+	}
+}
@@ -1,11 +1,15 @@
+use std::any::Any;
 use std::env;
-use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
+use std::panic;
 use std::path::Path;
-use std::process::Command;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 use std::time;
 
 extern crate termion;
@@ -16,6 +20,12 @@ extern crate serde_json;
 use serde::Deserialize;
 
 mod bfcc;
+mod driver;
+mod fuzz;
+mod interp;
+mod jit;
+
+use interp::{Config, Fault, Machine, Program};
 
 // The tests file structure is roughly. Artifacts are indeded to be plain text
 // and checked in
@@ -30,391 +40,523 @@ mod bfcc;
 //          |- stats
 //          \- bf.bf
 
+// `,` gets input one byte at a time and reads 0 once it's used up, which is
+// also what the bundled getchar gives back at the end of input. A case either
+// has one output (and maybe an input) or a list of runs, each one starting
+// the program over on its own input. Cases build for the host unless they
+// name a triple, the libc they get linked against along with them. A case with
+// a check list also goes through `bfcc --check`, every finding has to contain
+// one of the strings and every string has to turn up, so an empty list means
+// it has to come out clean.
 #[derive(Deserialize)]
 struct TestCase {
 	name: String,
-	output: String,
-	// input: Option<String>,
+	output: Option<String>,
+	input: Option<String>,
+	runs: Option<Vec<Run>>,
+	triple: Option<String>,
+	check: Option<Vec<String>>,
 	skip: Option<bool>,
 }
 
+// a libc for each triple the cases want, None being the host
+type Libcs = Vec<(Option<String>, Result<Vec<PathBuf>, String>)>;
+
+fn build_libc(triple: Option<&str>) -> Result<Vec<PathBuf>, String> {
+	let dir = Path::new(ARTIFACT_DIR).join(match triple {
+		Some(t) => format!("libc-{}", t),
+		None => "libc".to_string(),
+	});
+	fs::create_dir_all(&dir).unwrap();
+
+	let libs = driver::libc_into(triple, &dir)?;
+	for lib in libs.iter() {
+		driver::llvm_dis(lib)?;
+	}
+	Ok(libs)
+}
+
+#[derive(Deserialize, Clone)]
+struct Run {
+	#[serde(default)]
+	input: String,
+	output: String,
+}
+
+impl TestCase {
+	fn runs(&self) -> Vec<Run> {
+		match (&self.runs, &self.output) {
+			(Some(runs), None) if self.input.is_none() => runs.clone(),
+			(None, Some(output)) => vec![Run {
+				input: self.input.clone().unwrap_or_default(),
+				output: output.clone(),
+			}],
+			_ => panic!(
+				"{}: a test needs either output (and maybe input) or runs",
+				self.name
+			),
+		}
+	}
+}
+
 fn compile_ir(flags: &str, from: &str, to: &str) -> Result<(), String> {
-	let mut args = flags.split(" ").collect::<Vec<&str>>();
-	args.append(&mut vec!["-emit-llvm", "-I", ".", "-c", from, "-o", to]);
-	let res = Command::new("clang")
-		.args(args)
-		.output()
-		.map_err(|e| e.to_string())
-		.and_then(|o| match o.status.success() {
-			true => Ok(()),
-			false => Err(std::str::from_utf8(o.stderr.as_slice())
-				.unwrap()
-				.to_string()),
-		});
+	let flags = flags.split(" ").collect::<Vec<&str>>();
+	let res = driver::clang_bc(&flags, Path::new(from), Path::new(to));
 
 	// be nice and write out the human readable ir too
-	Command::new("llvm-dis").args([to]).output().unwrap();
+	if res.is_ok() {
+		driver::llvm_dis(Path::new(to)).unwrap();
+	}
 
 	res
 }
 
-fn compile_bf(path: &Path, target: &Path) -> String {
-	let code_out = bfcc::compile(path);
+fn compile_bf(
+	path: &Path,
+	libs: &[PathBuf],
+	target: &Path,
+) -> Result<String, String> {
+	let code_out = bfcc::compile_with(
+		&[path.to_path_buf()],
+		&bfcc::Options {
+			libs: libs.to_vec(),
+			..bfcc::Options::default()
+		},
+	)?
+	.code;
 
 	let mut file = File::create(target).unwrap();
 	file.write_all(code_out.as_bytes()).unwrap();
 
-	code_out
+	Ok(code_out)
+}
+
+// the libc a case links against and the cflags it builds with, which both
+// depend on its triple
+fn case_build<'a>(
+	info: &TestCase,
+	cflags: &str,
+	libcs: &'a Libcs,
+) -> Result<(&'a [PathBuf], String), String> {
+	let libs = match libcs.iter().find(|(t, _)| t == &info.triple) {
+		Some((_, Ok(libs))) => libs,
+		Some((_, Err(e))) => return Err(format!("libc: {}", e)),
+		None => unreachable!("main builds a libc for every triple"),
+	};
+	let cflags = match &info.triple {
+		Some(t) => format!("{} --target={}", cflags, t),
+		None => cflags.to_string(),
+	};
+	Ok((libs, cflags))
 }
 
 const ARTIFACT_DIR: &str = "./tests/artifacts";
 
-fn run_test(case: &fs::DirEntry, info: TestCase, cflags: &str, name: &str) {
-	if env::args().len() > 1 && env::args().find(|x| x == &info.name).is_none()
-	{
-		return;
+fn exec_config() -> Config {
+	Config {
+		tape: 10000,
+		clean_exit: true,
+		..Config::default()
 	}
+}
+
+// Run compiled output the way every test does, through the jit if jit is
+// Some(checked).
+fn execute(code: &str, input: &str, jit: Option<bool>) -> Result<Machine, Fault> {
+	let mut machine = Machine::new(Program::new(code)?, exec_config())
+		.with_input(input.as_bytes());
+	match jit {
+		Some(checked) => jit::run(&mut machine, checked)?,
+		None => machine.run()?,
+	};
+	Ok(machine)
+}
+
+#[derive(PartialEq)]
+enum Status {
+	Pass,
+	Fail,
+	Skip,
+}
+
+// How one case went at one opt level. Cases run on as many threads as
+// there are cores so nothing gets printed until main has it back in order.
+struct Report {
+	name: String,
+	status: Status,
+	// why it failed, ready to print
+	log: String,
+	// clang and bfcc
+	compile: time::Duration,
+	// every run through the interpreter, the jit's don't count
+	exec: time::Duration,
+}
+
+fn panic_message(p: &Box<dyn Any + Send>) -> String {
+	p.downcast_ref::<String>()
+		.cloned()
+		.or_else(|| p.downcast_ref::<&str>().map(|s| s.to_string()))
+		.unwrap_or_default()
+}
+
+fn run_test(
+	path: &Path,
+	info: &TestCase,
+	cflags: &str,
+	name: &str,
+	libcs: &Libcs,
+) -> Report {
+	let mut report = Report {
+		name: info.name.clone(),
+		status: Status::Fail,
+		log: String::new(),
+		compile: time::Duration::default(),
+		exec: time::Duration::default(),
+	};
 
 	let artifacts = format!(
 		"{}/{}/{}",
 		ARTIFACT_DIR,
 		name,
-		case.file_name().into_string().unwrap(),
+		path.file_name().unwrap().to_str().unwrap(),
 	);
 	fs::create_dir_all(Path::new(&artifacts)).unwrap();
 
 	if info.skip.unwrap_or(false) {
-		println!(
-			"{}{} skip {} {}",
-			color::Fg(color::Yellow),
-			style::Invert,
-			style::Reset,
-			info.name
-		);
-		return;
+		report.status = Status::Skip;
+		return report;
 	}
 
-	print!(
-		"{}{} test {} {}",
-		color::Fg(color::LightYellow),
-		style::Invert,
-		style::Reset,
-		info.name
-	);
-	io::stdout().flush().unwrap();
+	let (libs, cflags) = match case_build(info, cflags, libcs) {
+		Ok(b) => b,
+		Err(e) => {
+			report.log = format!("COMPILE ERROR\n{}\n", e);
+			return report;
+		}
+	};
 
-	let source = format!("{}", case.path().as_path().to_str().unwrap());
+	let source = format!("{}", path.to_str().unwrap());
 	let target = format!("{}/ir.bc", artifacts);
-
-	let cc = compile_ir(cflags, &source, &target);
-	if cc.is_err() {
-		println!(
-			"\r{}{} fail {} {}",
-			color::Fg(color::Red),
-			style::Invert,
-			style::Reset,
-			info.name
-		);
-		println!("{}", cc.unwrap_err());
-		return;
-	}
-
 	let bfout = format!("{}/bf.bf", artifacts);
-	let bf_code = compile_bf(Path::new(&target), Path::new(&bfout));
-
-	let bfbc = bf_bytecode(&bf_code);
-
-	let result = exec(bfbc);
-	if result.is_err() {
-		print!("\n");
-		println!("EXECUTE ERROR");
-		println!("{}", result.err().unwrap());
-		println!(
-			"\r{}{} fail {} {}",
-			color::Fg(color::Red),
-			style::Invert,
-			style::Reset,
-			info.name
-		);
 
-		return;
-	}
-
-	let result = result.unwrap();
-	if result.output != info.output {
-		print!("\n");
-		println!("OUTPUT MISMATCH");
-		println!("---");
-		println!("expected: {}", info.output);
-		println!("  actual: {}", result.output);
-		println!("---");
-		println!("source: {}", source);
-		println!("target: {}", bfout);
-		println!(
-			"\r{}{} fail {} {}",
-			color::Fg(color::Red),
-			style::Invert,
-			style::Reset,
-			info.name
+	let started = time::Instant::now();
+	let bf_code = compile_ir(&cflags, &source, &target).and_then(|_| {
+		compile_bf(Path::new(&target), libs, Path::new(&bfout))
+	});
+	report.compile = started.elapsed();
+
+	let bf_code = match bf_code {
+		Ok(code) => code,
+		Err(e) => {
+			report.log = format!("COMPILE ERROR\n{}\n", e);
+			return report;
+		}
+	};
+
+	if let Some(expected) = &info.check {
+		let findings = bfcc::check_with(
+			&[PathBuf::from(&target)],
+			&bfcc::Options {
+				libs: libs.to_vec(),
+				..bfcc::Options::default()
+			},
 		);
+		let findings = match findings {
+			Ok(f) => f.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+			Err(e) => {
+				report.log = format!("CHECK ERROR\n{}\n", e);
+				return report;
+			}
+		};
 
-		return;
+		let unexpected = findings
+			.iter()
+			.filter(|f| !expected.iter().any(|e| f.contains(e.as_str())));
+		let missing = expected
+			.iter()
+			.filter(|e| !findings.iter().any(|f| f.contains(e.as_str())));
+		let log = unexpected
+			.map(|f| format!("unexpected: {}\n", f))
+			.chain(missing.map(|e| format!("   missing: {}\n", e)))
+			.collect::<String>();
+		if !log.is_empty() {
+			report.log = format!("CHECK MISMATCH\n{}", log);
+			return report;
+		}
 	}
 
-	let mut stats = File::create(Path::new(&format!("{}/info", artifacts)))
-		.unwrap();
-
-	stats
-		.write_all(format!("steps: {}\n", result.steps).as_bytes())
-		.unwrap();
-
-	println!(
-		"\r{}{} pass {} {} {}({} ms){}",
-		color::Fg(color::Green),
-		style::Invert,
-		style::Reset,
-		info.name,
-		color::Fg(color::LightBlack),
-		0, // TODO
-		style::Reset
-	);
-}
+	let runs = info.runs();
+	let mut steps = vec![];
+	for (n, run) in runs.iter().enumerate() {
+		// which run broke, when there's more than one
+		let which = |what: &str| {
+			if runs.len() > 1 {
+				format!(
+					"{} (run {} of {}, input {:?})\n",
+					what,
+					n + 1,
+					runs.len(),
+					run.input
+				)
+			} else {
+				format!("{}\n", what)
+			}
+		};
 
-fn main() {
-	let mut cases = fs::read_dir("./tests/cases")
-		.unwrap()
-		.map(|r| r.unwrap())
-		.collect::<Vec<_>>();
-	cases.sort_by_key(|dir| dir.path());
+		let started = time::Instant::now();
+		let result = execute(&bf_code, &run.input, None);
+		report.exec += started.elapsed();
+
+		// the jit has to agree with the interpreter down to where things broke
+		if jit::unsupported(&exec_config()).is_none() {
+			let show = |r: &Result<Machine, Fault>| match r {
+				Ok(m) => format!("{:?} in {} steps", m.output, m.steps),
+				Err(f) => format!("{}", f),
+			};
+
+			let jitted = execute(&bf_code, &run.input, Some(true));
+			let same = match (&result, &jitted) {
+				(Ok(a), Ok(b)) => a.output == b.output && a.steps == b.steps,
+				(Err(a), Err(b)) => {
+					a.err == b.err && a.pc == b.pc && a.steps == b.steps
+				}
+				_ => false,
+			};
+
+			if !same {
+				report.log = which("JIT MISMATCH");
+				report.log += &format!("interpreter: {}\n", show(&result));
+				report.log += &format!("        jit: {}\n", show(&jitted));
+				return report;
+			}
 
-	println!(
-		"{}{} section {} {}",
-		color::Fg(color::Blue),
-		style::Invert,
-		style::Reset,
-		"-O0 no opt",
-	);
+			// unchecked code doesn't count steps and only promises anything
+			// for programs that run clean, so just the output has to match
+			if let Ok(ref a) = result {
+				let unchecked = execute(&bf_code, &run.input, Some(false));
+				let same = match unchecked {
+					Ok(ref b) => a.output == b.output,
+					Err(_) => false,
+				};
+
+				if !same {
+					report.log = which("UNCHECKED JIT MISMATCH");
+					report.log += &format!("interpreter: {}\n", show(&result));
+					report.log += &format!("  unchecked: {}\n", show(&unchecked));
+					return report;
+				}
+			}
+		}
 
-	for case in cases.iter() {
-		let content = fs::read_to_string(case.path()).unwrap();
+		let result = match result {
+			Ok(m) => m,
+			Err(f) => {
+				report.log = which("EXECUTE ERROR");
+				report.log += &format!("{}\n", f);
+				return report;
+			}
+		};
 
-		let from = content.find("TEST:").unwrap() + 5;
-		let to = content[from..].find("\n").unwrap() + from;
-		let info: TestCase = serde_json::from_str(&content[from..to]).unwrap();
+		let output: String = result.output.iter().map(|c| *c as char).collect();
+		if output != run.output {
+			report.log = which("OUTPUT MISMATCH");
+			report.log += &format!(
+				"---\nexpected: {}\n  actual: {}\n---\nsource: {}\ntarget: {}\n",
+				run.output, output, source, bfout
+			);
+			return report;
+		}
 
-		run_test(case, info, "-O0", "o0");
+		steps.push(result.steps);
 	}
 
-	println!(
-		"{}{} test {} {}",
-		color::Fg(color::Blue),
-		style::Invert,
-		style::Reset,
-		"-O1 opt level 1",
-	);
-
-	for case in cases.iter() {
-		let content = fs::read_to_string(case.path()).unwrap();
-
-		let from = content.find("TEST:").unwrap() + 5;
-		let to = content[from..].find("\n").unwrap() + from;
-		let info: TestCase = serde_json::from_str(&content[from..to]).unwrap();
+	let mut stats = File::create(Path::new(&format!("{}/info", artifacts)))
+		.unwrap();
 
-		run_test(case, info, "-O1", "o1");
+	// one line per run
+	for n in steps.iter() {
+		stats.write_all(format!("steps: {}\n", n).as_bytes()).unwrap();
 	}
-}
 
-#[derive(Debug)]
-enum InterpErr {
-	IntOverflow,
-	IntUnderflow,
-	MemOverflow,
-	MemUnderflow,
-	ExitMemNonZero,
+	report.status = Status::Pass;
+	report
 }
 
-impl fmt::Display for InterpErr {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		write!(
-			f,
-			"{}",
-			match self {
-				IntOverflow => "cell value overflow (undefined behavior)",
-				IntUnderflow => "cell value underflow (undefined behavior)",
-				MemOverflow => "ran out of memory",
-				MemUnderflow =>
-					"decrement memory pointer past first cell (undefined behavior)",
-				ExitMemNonZero => "all memory must be zeroed at program exit",
-			}
-		)
+fn print_report(report: &Report) {
+	let ms = |d: time::Duration| d.as_millis();
+	match report.status {
+		Status::Skip => println!(
+			"{}{} skip {} {}",
+			color::Fg(color::Yellow),
+			style::Invert,
+			style::Reset,
+			report.name
+		),
+		Status::Pass => println!(
+			"{}{} pass {} {} {}(compile {} ms, run {} ms){}",
+			color::Fg(color::Green),
+			style::Invert,
+			style::Reset,
+			report.name,
+			color::Fg(color::LightBlack),
+			ms(report.compile),
+			ms(report.exec),
+			style::Reset
+		),
+		Status::Fail => {
+			print!("{}", report.log);
+			println!(
+				"{}{} fail {} {} {}(compile {} ms, run {} ms){}",
+				color::Fg(color::Red),
+				style::Invert,
+				style::Reset,
+				report.name,
+				color::Fg(color::LightBlack),
+				ms(report.compile),
+				ms(report.exec),
+				style::Reset
+			);
+		}
 	}
 }
 
-struct ExecResult {
-	output: String,
-	steps: usize,
-}
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
 
-#[derive(Clone, Copy, Debug)]
-enum COps {
-	Add(i32),
-	Mov(i64),
-	Putchar,
-	JmpIfZ(u64),
-	JmpIfNZ(u64),
-	//Loop(Vec<COps>)
-}
+	// the libc most tests get linked against, built for the host like they
+	// are
+	let libs = build_libc(None).unwrap();
 
-fn bf_bytecode(code: &str) -> Vec<COps> {
-	let mut opsout = Vec::<COps>::new();
-
-	let chars: Vec<char> = code.chars().collect();
-
-	for c in chars.iter() {
-		opsout.push(match c {
-			'+' => COps::Add(1),
-			'-' => COps::Add(-1),
-			'>' => COps::Mov(1),
-			'<' => COps::Mov(-1),
-			'[' => COps::JmpIfZ(0),
-			']' => COps::JmpIfNZ(0),
-			'.' => COps::Putchar,
-			',' => panic!("TODO"),
-			_ => continue,
-		})
+	if args.first().map_or(false, |a| a == "--fuzz") {
+		fuzz::main(&args[1..], &libs, exec_config());
+		return;
 	}
 
-	// combine similar
-	let mut into = vec![opsout[0]];
-	for op in opsout.iter().skip(1) {
-		let repl = match (into[into.len() - 1], op) {
-			(COps::Add(a), COps::Add(b)) => Some(COps::Add(a + b)),
-			(COps::Mov(a), COps::Mov(b)) => Some(COps::Mov(a + b)),
-			_ => None,
-		};
-
-		if repl.is_some() {
-			let l = into.len();
-			into[l - 1] = repl.unwrap();
+	// anything that isn't a flag is the name of a test to run instead of all
+	let mut jobs = thread::available_parallelism().map_or(1, |n| n.get());
+	let mut names = vec![];
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		if arg == "-j" || arg == "--jobs" {
+			jobs = args
+				.next()
+				.and_then(|n| n.parse().ok())
+				.filter(|n| *n > 0)
+				.unwrap_or_else(|| {
+					eprintln!("verify: {} expects a thread count", arg);
+					process::exit(2);
+				});
 		} else {
-			into.push(*op);
+			names.push(arg);
 		}
 	}
-	let mut opsout = into;
 
-	// actually resolve ops
-	opsout = opsout
-		.iter()
-		.enumerate()
-		.map(|(i, op)| match op {
-			COps::JmpIfZ(_) => {
-				let mut d = 1;
-				for j in (i + 1)..opsout.len() {
-					d += match opsout[j] {
-						COps::JmpIfZ(_) => 1,
-						COps::JmpIfNZ(_) => -1,
-						_ => 0,
-					};
-
-					if d == 0
-						&& match opsout[j] {
-							COps::JmpIfNZ(_) => true,
-							_ => false,
-						} {
-						return COps::JmpIfZ(j as u64);
-					}
-				}
-
-				panic!("unbalanced?");
-			}
-			COps::JmpIfNZ(_) => {
-				let mut d = 1;
-				for j in (0..i).rev() {
-					d += match opsout[j] {
-						COps::JmpIfNZ(_) => 1,
-						COps::JmpIfZ(_) => -1,
-						_ => 0,
-					};
-
-					if d == 0
-						&& match opsout[j] {
-							COps::JmpIfZ(_) => true,
-							_ => false,
-						} {
-						return COps::JmpIfNZ(j as u64);
-					}
-				}
-
-				panic!("unbalanced?");
-			}
-			_ => *op,
+	let mut cases = fs::read_dir("./tests/cases")
+		.unwrap()
+		.map(|r| r.unwrap().path())
+		.collect::<Vec<_>>();
+	cases.sort();
+
+	let cases = cases
+		.into_iter()
+		.map(|path| {
+			let content = fs::read_to_string(&path).unwrap();
+
+			let from = content.find("TEST:").unwrap() + 5;
+			let to = content[from..].find("\n").unwrap() + from;
+			let info: TestCase =
+				serde_json::from_str(&content[from..to]).unwrap();
+			(path, info)
 		})
-		.collect();
-
-	opsout
-}
-
-fn exec(ops: Vec<COps>) -> Result<ExecResult, InterpErr> {
-	let mut pc = 0;
-	let mut mp = 0;
-	let mut steps = 0;
-
-	let mut mem: [u8; 10000] = [0; 10000];
-
-	let mut output: Vec<char> = vec![];
+		.filter(|(_, info)| names.is_empty() || names.contains(&info.name))
+		.collect::<Vec<_>>();
 
-	while pc < ops.len() {
-		match ops[pc] {
-			COps::Putchar => output.push(mem[mp] as char),
+	// a clang without some triple only fails the cases that want it
+	let mut libcs: Libcs = vec![(None, Ok(libs))];
+	for (_, info) in cases.iter() {
+		if !libcs.iter().any(|(t, _)| t == &info.triple) {
+			let libs = build_libc(info.triple.as_ref().map(|t| t.as_str()));
+			libcs.push((info.triple.clone(), libs));
+		}
+	}
 
-			COps::Add(n) => {
-				let v = mem[mp] as isize + n as isize;
-				if v > 255 {
-					return Err(InterpErr::IntOverflow);
-				} else if v < 0 {
-					return Err(InterpErr::IntUnderflow);
-				}
-				mem[mp] = v as u8;
-			}
+	// (cflags, artifact dir, heading)
+	let sections = [("-O0", "o0", "-O0 no opt"), ("-O1", "o1", "-O1 opt level 1")];
 
-			COps::Mov(n) => {
-				let to = mp as isize + n as isize;
-				if to >= mem.len() as isize {
-					return Err(InterpErr::MemOverflow);
-				}
+	let work = sections
+		.iter()
+		.flat_map(|section| cases.iter().map(move |case| (section, case)))
+		.collect::<Vec<_>>();
 
-				if to < 0 {
-					return Err(InterpErr::MemUnderflow);
+	let started = time::Instant::now();
+	let next = AtomicUsize::new(0);
+	let (tx, rx) = mpsc::channel();
+	let mut counts = [0; 3];
+
+	thread::scope(|scope| {
+		for _ in 0..jobs.min(work.len()) {
+			let (tx, next, work, libcs) = (tx.clone(), &next, &work, &libcs);
+			scope.spawn(move || loop {
+				let i = next.fetch_add(1, Ordering::SeqCst);
+				if i >= work.len() {
+					break;
 				}
 
-				mp = to as usize;
-			}
-
-			COps::JmpIfZ(a) => {
-				if mem[mp] == 0 {
-					pc = a as usize;
+				let ((cflags, dir, _), (path, info)) = work[i];
+				let report = panic::catch_unwind(|| {
+					run_test(path, info, cflags, dir, libcs)
+				})
+				.unwrap_or_else(|p| Report {
+					name: info.name.clone(),
+					status: Status::Fail,
+					log: format!("PANIC\n{}\n", panic_message(&p)),
+					compile: time::Duration::default(),
+					exec: time::Duration::default(),
+				});
+				tx.send((i, report)).unwrap();
+			});
+		}
+		drop(tx);
+
+		// print in the same order as running serially would, as soon as
+		// everything before a report is in
+		let mut done: Vec<Option<Report>> = work.iter().map(|_| None).collect();
+		let mut shown = 0;
+		for (i, report) in rx {
+			done[i] = Some(report);
+			while shown < work.len() && done[shown].is_some() {
+				let (section, _) = work[shown];
+				if shown == 0 || work[shown - 1].0 != section {
+					println!(
+						"{}{} section {} {}",
+						color::Fg(color::Blue),
+						style::Invert,
+						style::Reset,
+						section.2,
+					);
 				}
-			}
 
-			COps::JmpIfNZ(a) => {
-				if mem[mp] != 0 {
-					pc = a as usize;
-				}
+				let report = done[shown].take().unwrap();
+				print_report(&report);
+				counts[match report.status {
+					Status::Pass => 0,
+					Status::Fail => 1,
+					Status::Skip => 2,
+				}] += 1;
+				shown += 1;
 			}
-		};
+		}
+	});
 
-		pc += 1;
-		steps += 1;
-	}
+	println!(
+		"{} passed, {} failed, {} skipped in {} ms",
+		counts[0],
+		counts[1],
+		counts[2],
+		started.elapsed().as_millis()
+	);
 
-	for i in mem {
-		if i != 0 {
-			return Err(InterpErr::ExitMemNonZero);
-		}
+	if counts[1] > 0 {
+		process::exit(1);
 	}
-
-	Ok(ExecResult {
-		output: output.iter().collect(),
-		steps: steps,
-	})
 }